@@ -1,9 +1,13 @@
 pub(crate) mod camera_controller;
+pub(crate) mod floating_origin;
 use bevy::{
     core_pipeline::{bloom::BloomSettings, tonemapping::Tonemapping},
     prelude::*,
 };
 use camera_controller::{CameraController, CameraControllerPlugin};
+use floating_origin::FloatingOriginPlugin;
+
+use crate::scene::CameraPose;
 
 #[derive(Component)]
 /// Marker component for the main camera.
@@ -13,7 +17,7 @@ pub struct MainCamera;
 pub struct CustomCameraPlugin;
 impl Plugin for CustomCameraPlugin {
     fn build(&self, app: &mut App) {
-        app.add_plugins(CameraControllerPlugin)
+        app.add_plugins((CameraControllerPlugin, FloatingOriginPlugin))
             .add_systems(Startup, setup_camera)
             //.add_systems(Update, draw_cursor)
             ;
@@ -22,6 +26,7 @@ impl Plugin for CustomCameraPlugin {
 
 /// Setup the camera in a 3D scene.
 fn setup_camera(mut commands: Commands) {
+    let pose = CameraPose::default();
     commands.spawn((
         Camera3dBundle {
             camera: Camera {
@@ -29,7 +34,8 @@ fn setup_camera(mut commands: Commands) {
                 ..default()
             },
             tonemapping: Tonemapping::TonyMcMapface, // 2. Using a tonemapper that desaturates to white is recommended
-            transform: Transform::from_xyz(0.0, 500.0, 0.0).looking_at(Vec3::ZERO, -Vec3::Z),
+            transform: Transform::from_translation(pose.position.as_vec3())
+                .with_rotation(pose.rotation),
 
             ..default()
         },
@@ -0,0 +1,75 @@
+use bevy::{math::DVec3, prelude::*, transform::TransformSystem};
+
+use super::MainCamera;
+use crate::planets::planet_bundle::Position;
+
+/// Once the camera is this far from the render origin, the origin is moved to
+/// the camera so that rendered translations stay small.
+const RECENTER_DISTANCE: f32 = 1_000.;
+
+#[derive(Resource, Default, Debug, Clone, Copy)]
+/// The position, in the simulation, of the origin of the rendered world.
+///
+/// Bodies are simulated in double precision but rendered in single precision:
+/// their `Transform`s are derived from their `Position` relative to this origin,
+/// which follows the camera so that nearby bodies never lose precision.
+pub struct FloatingOrigin(pub DVec3);
+
+impl FloatingOrigin {
+    /// Converts a position in the simulation to a rendered translation.
+    pub fn to_render(self, position: DVec3) -> Vec3 {
+        (position - self.0).as_vec3()
+    }
+
+    /// Converts a rendered translation to a position in the simulation.
+    pub fn to_simulation(self, translation: Vec3) -> DVec3 {
+        self.0 + translation.as_dvec3()
+    }
+}
+
+/// Plugin keeping the rendered world centered on the camera.
+pub struct FloatingOriginPlugin;
+
+impl Plugin for FloatingOriginPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<FloatingOrigin>().add_systems(
+            PostUpdate,
+            (recenter_origin, sync_transforms)
+                .chain()
+                .before(TransformSystem::TransformPropagate),
+        );
+    }
+}
+
+/// Moves the origin to the camera once it wandered too far, shifting the other
+/// root entities without a `Position` (e.g. the background stars) to match.
+#[allow(clippy::type_complexity)]
+fn recenter_origin(
+    mut origin: ResMut<FloatingOrigin>,
+    mut query_cam: Query<&mut Transform, With<MainCamera>>,
+    mut query_roots: Query<
+        &mut Transform,
+        (Without<Parent>, Without<Position>, Without<MainCamera>),
+    >,
+) {
+    let Ok(mut camera) = query_cam.get_single_mut() else {
+        return;
+    };
+    let offset = camera.translation;
+    if offset.length() < RECENTER_DISTANCE {
+        return;
+    }
+
+    origin.0 += offset.as_dvec3();
+    camera.translation = Vec3::ZERO;
+    for mut transform in &mut query_roots {
+        transform.translation -= offset;
+    }
+}
+
+/// Derives the rendered translation of every body from its `Position`.
+fn sync_transforms(origin: Res<FloatingOrigin>, mut query: Query<(&Position, &mut Transform)>) {
+    for (position, mut transform) in &mut query {
+        transform.translation = origin.to_render(position.0);
+    }
+}
@@ -0,0 +1,238 @@
+use std::fs;
+use std::io::{BufWriter, Write};
+use std::path::PathBuf;
+
+use crate::scene::SceneSource;
+use crate::simulation::{
+    diagnostics::Diagnostics, gravity::ForceSolver, integrators::IntegratorKind,
+};
+
+pub mod benchmark;
+
+/// Usage of the headless mode, printed on invalid arguments.
+const USAGE: &str = "Usage: solarust --headless <scene.json | --preset NAME> [--steps N] \
+[--dt SECONDS] [--integrator euler|leapfrog|verlet|rk4] [--solver pairwise|barnes-hut] \
+[--theta ANGLE] [--output <file.json>] [--diagnostics <file.csv>]";
+
+/// Options of the headless mode.
+struct HeadlessOptions {
+    scene: SceneSource,
+    output: Option<String>,
+    diagnostics: Option<String>,
+    steps: usize,
+    /// Overrides the time step of the scene.
+    dt: Option<f64>,
+    /// Overrides the integrator of the scene.
+    integrator: Option<IntegratorKind>,
+    solver: Option<ForceSolver>,
+    opening_angle: Option<f64>,
+}
+
+impl HeadlessOptions {
+    /// Parses the command line arguments (without the program name).
+    fn parse(args: &[String]) -> Result<Self, String> {
+        let mut scene = None;
+        let mut output = None;
+        let mut diagnostics = None;
+        let mut steps = 1000;
+        let mut dt = None;
+        let mut integrator = None;
+        let mut solver = None;
+        let mut opening_angle = None;
+
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            let mut value = || {
+                args.next()
+                    .ok_or_else(|| format!("Missing value for {}\n{}", arg, USAGE))
+            };
+            match arg.as_str() {
+                "--headless" => (),
+                "--steps" => {
+                    steps = value()?
+                        .parse()
+                        .map_err(|e| format!("Invalid step count : {}", e))?
+                }
+                "--dt" => {
+                    dt = Some(
+                        value()?
+                            .parse()
+                            .map_err(|e| format!("Invalid time step : {}", e))?,
+                    )
+                }
+                "--integrator" => integrator = Some(value()?.parse()?),
+                "--solver" => solver = Some(value()?.parse()?),
+                "--theta" => {
+                    opening_angle = Some(
+                        value()?
+                            .parse()
+                            .map_err(|e| format!("Invalid opening angle : {}", e))?,
+                    )
+                }
+                "--output" => output = Some(value()?.clone()),
+                "--diagnostics" => diagnostics = Some(value()?.clone()),
+                "--preset" if scene.is_none() => {
+                    scene = Some(SceneSource::Preset(value()?.parse()?))
+                }
+                path if !path.starts_with("--") && scene.is_none() => {
+                    scene = Some(SceneSource::File(PathBuf::from(path)))
+                }
+                _ => return Err(format!("Unexpected argument {}\n{}", arg, USAGE)),
+            }
+        }
+
+        Ok(Self {
+            scene: scene.ok_or_else(|| format!("Missing scene file or preset\n{}", USAGE))?,
+            output,
+            diagnostics,
+            steps,
+            dt,
+            integrator,
+            solver,
+            opening_angle,
+        })
+    }
+}
+
+/// Runs the simulation without any window: loads a scene, advances it
+/// by a fixed number of steps and writes the final state as a scene file,
+/// either to the given output file or to stdout.
+pub fn run(args: &[String]) -> Result<(), String> {
+    let options = HeadlessOptions::parse(args)?;
+
+    let mut app_data = options.scene.read().map_err(|e| e.to_string())?;
+
+    if let Some(solver) = options.solver {
+        app_data.physics.force_solver = solver;
+    }
+    if let Some(opening_angle) = options.opening_angle {
+        app_data.physics.opening_angle = opening_angle;
+    }
+    // the windowed app steps the scene by its own settings, so runs match it by default
+    let dt = options.dt.unwrap_or(app_data.settings.dt);
+    let mut simulation =
+        app_data.to_simulation(options.integrator.unwrap_or(app_data.settings.integrator));
+    let mut diagnostics = match &options.diagnostics {
+        Some(path) => {
+            let file = fs::File::create(path)
+                .map_err(|e| format!("Error while creating {} : {}", path, e))?;
+            let mut csv = BufWriter::new(file);
+            writeln!(csv, "{}", Diagnostics::CSV_HEADER).map_err(|e| format!("Error : {}", e))?;
+            writeln!(
+                csv,
+                "{}",
+                simulation.diagnostics().csv_row(simulation.elapsed)
+            )
+            .map_err(|e| format!("Error : {}", e))?;
+            Some(csv)
+        }
+        None => None,
+    };
+    for _ in 0..options.steps {
+        let collisions = simulation.advance(dt).collisions;
+        for collision in &collisions {
+            eprintln!(
+                "{:.3} : {} and {} collided ({})",
+                simulation.elapsed,
+                app_data.celestial_bodies[collision.first].body_data.name,
+                app_data.celestial_bodies[collision.second].body_data.name,
+                collision.response
+            );
+        }
+        app_data.apply_collisions(&collisions);
+        if let Some(csv) = diagnostics.as_mut() {
+            writeln!(
+                csv,
+                "{}",
+                simulation.diagnostics().csv_row(simulation.elapsed)
+            )
+            .map_err(|e| format!("Error : {}", e))?;
+        }
+    }
+    if let Some(mut csv) = diagnostics {
+        csv.flush().map_err(|e| format!("Error : {}", e))?;
+    }
+    app_data.update_from_simulation(&simulation);
+    app_data.settings.integrator = simulation.integrator;
+    app_data.settings.dt = dt;
+
+    let serialized_data = app_data.to_json().map_err(|e| format!("Error : {}", e))?;
+    match options.output {
+        Some(path) => {
+            fs::write(&path, serialized_data).map_err(|e| format!("Error : {}", e))?;
+            eprintln!(
+                "Simulated {} steps ({:.3}s), final state saved to {}",
+                options.steps, simulation.elapsed, path
+            );
+        }
+        None => println!("{}", serialized_data),
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scene::presets::Preset;
+
+    fn args(line: &str) -> Vec<String> {
+        line.split_whitespace().map(String::from).collect()
+    }
+
+    #[test]
+    fn options_are_parsed() {
+        let options = HeadlessOptions::parse(&args(
+            "--headless scene.json --steps 10 --dt 0.5 --integrator rk4 --solver barnes-hut \
+             --theta 0.7 --output out.json --diagnostics out.csv",
+        ))
+        .unwrap_or_else(|e| panic!("{}", e));
+
+        assert_eq!(
+            options.scene,
+            SceneSource::File(PathBuf::from("scene.json"))
+        );
+        assert_eq!(options.steps, 10);
+        assert_eq!(options.dt, Some(0.5));
+        assert_eq!(options.integrator, Some(IntegratorKind::Rk4));
+        assert_eq!(options.solver, Some(ForceSolver::BarnesHut));
+        assert_eq!(options.opening_angle, Some(0.7));
+        assert_eq!(options.output.as_deref(), Some("out.json"));
+        assert_eq!(options.diagnostics.as_deref(), Some("out.csv"));
+    }
+
+    #[test]
+    fn presets_are_parsed() {
+        let options = HeadlessOptions::parse(&args("--preset figure-eight"))
+            .unwrap_or_else(|e| panic!("{}", e));
+        assert_eq!(options.scene, SceneSource::Preset(Preset::FigureEight));
+        assert_eq!(options.steps, 1000);
+        assert_eq!(options.dt, None);
+    }
+
+    #[test]
+    fn invalid_arguments_are_rejected() {
+        let error = |line| HeadlessOptions::parse(&args(line)).err().unwrap();
+
+        assert!(error("scene.json --fast").starts_with("Unexpected argument --fast"));
+        assert!(error("scene.json --steps").starts_with("Missing value for --steps"));
+        assert!(error("scene.json --steps many").starts_with("Invalid step count"));
+        assert!(error("--steps 10").starts_with("Missing scene file or preset"));
+        assert!(error("--preset trojans scene.json").starts_with("Unexpected argument scene.json"));
+        assert!(error("scene.json --preset trojans").starts_with("Unexpected argument --preset"));
+        assert!(error("--preset nowhere").starts_with("Unknown preset nowhere"));
+    }
+
+    #[test]
+    fn runs_are_deterministic() {
+        let outputs = ["first", "second"].map(|name| {
+            let path = std::env::temp_dir().join(format!("solarust_headless_{}.json", name));
+            let mut arguments = args("--preset pythagorean --steps 200 --output");
+            arguments.push(path.to_string_lossy().into_owned());
+            run(&arguments).unwrap_or_else(|e| panic!("{}", e));
+            let output = fs::read_to_string(&path).unwrap();
+            fs::remove_file(&path).unwrap();
+            output
+        });
+        assert_eq!(outputs[0], outputs[1]);
+    }
+}
@@ -0,0 +1,68 @@
+use std::time::{Duration, Instant};
+
+use bevy::math::DVec3;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use crate::simulation::gravity::{ForceSolver, Gravity};
+
+/// The body counts the force evaluation is measured at.
+const BODY_COUNTS: [usize; 3] = [100, 1_000, 10_000];
+
+/// How long each configuration is measured for, at least.
+const MEASURE_DURATION: Duration = Duration::from_millis(500);
+
+/// Measures the average duration of one force evaluation.
+fn measure(gravity: &Gravity, positions: &[DVec3]) -> Duration {
+    let start = Instant::now();
+    let mut iterations = 0;
+    while iterations == 0 || start.elapsed() < MEASURE_DURATION {
+        std::hint::black_box(gravity.accelerations(positions));
+        iterations += 1;
+    }
+    start.elapsed() / iterations
+}
+
+/// Compares serial and multithreaded force evaluation for both solvers
+/// on random clouds of bodies, and prints the results as a table.
+pub fn run() {
+    let mut rng = StdRng::seed_from_u64(0);
+
+    println!("| Bodies | Solver | Serial (ms) | Parallel (ms) | Speed-up |");
+    println!("|-------:|--------|------------:|--------------:|---------:|");
+    for count in BODY_COUNTS {
+        let positions = (0..count)
+            .map(|_| {
+                DVec3::new(
+                    rng.gen_range(-1000.0..1000.0),
+                    rng.gen_range(-1000.0..1000.0),
+                    rng.gen_range(-1000.0..1000.0),
+                )
+            })
+            .collect::<Vec<_>>();
+        let masses = vec![1.; count];
+        let softenings = vec![0.1; count];
+
+        for solver in ForceSolver::ALL {
+            let mut gravity = Gravity {
+                g: 1.,
+                masses: &masses,
+                softenings: &softenings,
+                solver,
+                opening_angle: 0.5,
+                parallel: false,
+            };
+            let serial = measure(&gravity, &positions);
+            gravity.parallel = true;
+            let parallel = measure(&gravity, &positions);
+
+            println!(
+                "| {} | {} | {:.3} | {:.3} | {:.2}x |",
+                count,
+                solver,
+                serial.as_secs_f64() * 1000.,
+                parallel.as_secs_f64() * 1000.,
+                serial.as_secs_f64() / parallel.as_secs_f64()
+            );
+        }
+    }
+}
@@ -3,10 +3,39 @@ use camera::CustomCameraPlugin;
 use planets::PlanetPlugin;
 use ui::UIPlugin;
 mod camera;
+mod headless;
 mod planets;
+mod scene;
+mod simulation;
 mod ui;
 fn main() {
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    if args.iter().any(|arg| arg == "--benchmark") {
+        headless::benchmark::run();
+        return;
+    }
+    if args.iter().any(|arg| arg == "--headless") {
+        if let Err(e) = headless::run(&args) {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    let mut scene_args = match ui::io::SceneArgs::parse(&args) {
+        Ok(scene_args) => scene_args,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
+    if let Err(e) = scene_args.read_scene() {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    }
+
     App::new()
+        .insert_resource(scene_args)
         .add_plugins(DefaultPlugins)
         .add_plugins((UIPlugin, CustomCameraPlugin, PlanetPlugin))
         .run();
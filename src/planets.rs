@@ -1,12 +1,21 @@
-use std::collections::VecDeque;
+use std::time::Duration;
 
-use bevy::prelude::*;
+use bevy::{prelude::*, time::common_conditions::on_timer};
 
-use planet_bundle::CelestialBodyData;
+use particles::{ParticleCloud, ParticlePlugin};
+use planet_bundle::{Acceleration, CelestialBodyData, OrbitParent, Position};
 use rand::Rng;
 
+use crate::simulation::{
+    collisions::{retain_survivors, CollisionResponse},
+    integrators::IntegratorKind,
+    orbits::orbit_parents,
+    physics_config::PhysicsConfig,
+    Simulation,
+};
 use crate::ui::SimulationState;
 
+pub mod particles;
 pub mod planet_bundle;
 
 /// This plugin is responsible for setting up the simulation
@@ -16,13 +25,24 @@ pub struct PlanetPlugin;
 impl Plugin for PlanetPlugin {
     fn build(&self, app: &mut bevy::prelude::App) {
         app.insert_resource(ClearColor(Color::BLACK))
+            .init_resource::<IntegratorKind>()
+            .init_resource::<PhysicsConfig>()
+            .init_resource::<SubStepStats>()
+            .init_resource::<SimulationClock>()
+            .add_event::<CollisionEvent>()
+            .add_plugins(ParticlePlugin)
             .add_systems(Startup, setup_simple_stars)
-            .add_systems(Update, (rotate, radius_changed))
+            .add_systems(
+                Update,
+                (
+                    rotate,
+                    radius_changed,
+                    update_orbit_parents.run_if(on_timer(Duration::from_millis(250))),
+                ),
+            )
             .add_systems(
                 FixedUpdate,
-                (update_velocities, update_positions)
-                    .chain()
-                    .run_if(in_state(SimulationState::Running)),
+                integrate_bodies.run_if(in_state(SimulationState::Running).or_else(step_requested)),
             );
     }
 }
@@ -35,44 +55,203 @@ fn rotate(mut query: Query<&mut Transform, With<CelestialBodyData>>, time: Res<T
     }
 }
 
-/// Updates the velocities of bodies by calculating their gravitational
-/// forces on each other.
-fn update_velocities(
-    mut query: Query<(&mut CelestialBodyData, &Transform), With<CelestialBodyData>>,
+#[derive(Resource, Default)]
+/// Statistics about the last simulation step.
+pub struct SubStepStats {
+    /// How many sub-steps the last `FixedUpdate` tick was split in, time warp included.
+    pub substeps: usize,
+    /// The sub-step the adaptive stepping will try first on the next tick.
+    pub next_substep: Option<f64>,
+}
+
+#[derive(Resource)]
+/// How fast the simulated time flows.
+pub struct SimulationClock {
+    /// How many simulated seconds pass per real second.
+    pub time_warp: f32,
+    /// The time warp applied on the last tick, lower than `time_warp` when
+    /// the steps did not fit in the step budget.
+    pub applied_time_warp: f32,
+    /// The simulated time elapsed since the application started.
+    pub elapsed: f64,
+    /// Whether a single step was requested while paused.
+    pub step_once: bool,
+}
+
+impl Default for SimulationClock {
+    fn default() -> Self {
+        Self {
+            time_warp: 1.,
+            applied_time_warp: 1.,
+            elapsed: 0.,
+            step_once: false,
+        }
+    }
+}
+
+impl SimulationClock {
+    /// The smallest and largest time warps.
+    pub const TIME_WARP_RANGE: std::ops::RangeInclusive<f32> = 0.1..=10_000.;
+
+    /// Splits a tick of `dt` real seconds into steps no longer than `dt`,
+    /// returning their length and number.
+    ///
+    /// A single step requested by `step_once` is never warped.
+    fn steps(&self, dt: f64) -> (f64, usize) {
+        if self.step_once {
+            return (dt, 1);
+        }
+        let time_warp = self.time_warp as f64;
+        let steps = time_warp.ceil().max(1.);
+        (dt * time_warp / steps, steps as usize)
+    }
+}
+
+/// Run condition checking whether a single step was requested.
+fn step_requested(clock: Res<SimulationClock>) -> bool {
+    clock.step_once
+}
+
+#[derive(Event, Debug, Clone)]
+/// Sent whenever two bodies collide.
+pub struct CollisionEvent {
+    /// The name of the surviving body when merging, of the first body otherwise.
+    pub first_name: String,
+    /// The name of the absorbed body when merging, of the second body otherwise.
+    pub second_name: String,
+    /// The speed at which the bodies approached each other.
+    pub relative_speed: f64,
+    /// How the collision was resolved.
+    pub response: CollisionResponse,
+}
+
+/// Advances the bodies by one fixed time step by driving a `Simulation`
+/// built from the ECS state, then applies the collisions to the entities.
+///
+/// The particles of the `ParticleCloud`s are simulated after the bodies, as
+/// massless bodies; those hitting a body are removed.
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+fn integrate_bodies(
+    mut commands: Commands,
+    mut query: Query<(
+        Entity,
+        &mut CelestialBodyData,
+        &mut Position,
+        &mut Acceleration,
+        &Handle<StandardMaterial>,
+    )>,
+    mut query_clouds: Query<&mut ParticleCloud>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut collision_events: EventWriter<CollisionEvent>,
+    mut substep_stats: ResMut<SubStepStats>,
+    mut clock: ResMut<SimulationClock>,
+    integrator: Res<IntegratorKind>,
+    physics_config: Res<PhysicsConfig>,
     time: Res<Time>,
 ) {
-    let g = 1.;
-
-    let mut operations = VecDeque::new();
-    for (bd1, tfm1) in &query {
-        let mut total_velocity_to_add = Vec3::ZERO;
-        for (bd2, tfm2) in query.iter() {
-            if tfm1 == tfm2 {
-                continue;
+    let mut bodies = query.iter_mut().collect::<Vec<_>>();
+    let mut simulation = Simulation::new(*physics_config, *integrator);
+    simulation.bodies = bodies
+        .iter()
+        .map(|(_, bd, position, _, _)| bd.to_body(position.0))
+        .collect();
+    let mut clouds = query_clouds.iter_mut().collect::<Vec<_>>();
+    // the cloud of each simulated body, `None` for the actual bodies
+    let mut owners = vec![None; bodies.len()];
+    for (index, cloud) in clouds.iter().enumerate() {
+        simulation.bodies.extend(cloud.bodies());
+        owners.extend(std::iter::repeat_n(Some(index), cloud.positions.len()));
+    }
+
+    simulation.substep = substep_stats.next_substep;
+
+    let (dt, steps) = clock.steps(time.delta_seconds_f64());
+    let report = simulation.advance_many(dt, steps);
+    let simulated = dt * report.steps as f64;
+    clock.elapsed += simulated;
+    if !clock.step_once {
+        clock.applied_time_warp = (simulated / time.delta_seconds_f64()) as f32;
+    }
+    clock.step_once = false;
+    let collisions = report.collisions;
+    substep_stats.substeps = report.substeps;
+    substep_stats.next_substep = simulation.substep;
+
+    for collision in &collisions {
+        if collision.second >= bodies.len() {
+            // a particle hit a body
+            continue;
+        }
+        let (second, second_data, _, _, _) = &bodies[collision.second];
+        let (second, second_name, second_color) =
+            (*second, second_data.name.clone(), second_data.color);
+        let (_, first_data, _, _, material) = &mut bodies[collision.first];
+
+        if collision.absorbed().is_some() {
+            first_data.blend_color(second_color, collision.first_mass, collision.second_mass);
+            if let Some(material) = materials.get_mut(material.id()) {
+                material.base_color = Color::rgb_from_array(first_data.color);
+                material.emissive = material.base_color * first_data.emissive_factor;
             }
-            total_velocity_to_add += bd1.compute_velocity(
-                tfm1.translation,
-                tfm2.translation,
-                bd2.mass,
-                g,
-                time.delta_seconds(),
-            );
+            commands.entity(second).despawn_recursive();
         }
-        operations.push_back(total_velocity_to_add);
+
+        collision_events.send(CollisionEvent {
+            first_name: first_data.name.clone(),
+            second_name,
+            relative_speed: collision.relative_speed,
+            response: collision.response,
+        });
     }
 
-    for (mut db, _) in &mut query {
-        db.velocity += operations.pop_front().unwrap();
+    retain_survivors(&mut bodies, &collisions);
+    retain_survivors(&mut owners, &collisions);
+    let mut simulated = simulation.bodies.into_iter().zip(owners);
+    for ((_, bd, position, acceleration, _), (body, _)) in bodies.iter_mut().zip(&mut simulated) {
+        acceleration.0 = body.acceleration;
+        position.0 = body.position;
+        bd.velocity = body.velocity;
+        bd.mass = body.mass;
+        bd.radius = body.radius;
+    }
+
+    for cloud in &mut clouds {
+        cloud.positions.clear();
+        cloud.velocities.clear();
+    }
+    for (body, owner) in simulated {
+        let cloud = &mut clouds[owner.expect("bodies come before particles")];
+        cloud.positions.push(body.position);
+        cloud.velocities.push(body.velocity);
     }
 }
 
-/// Updates the positions of bodies based on their velocities.
-fn update_positions(
-    mut query: Query<(&CelestialBodyData, &mut Transform), With<CelestialBodyData>>,
-    time: Res<Time>,
+/// Updates the orbit parents of the bodies from their Hill spheres, keeping
+/// the parents pinned by the user.
+fn update_orbit_parents(
+    mut query: Query<(Entity, &CelestialBodyData, &Position, &mut OrbitParent)>,
 ) {
-    for (bd, mut tfm) in &mut query {
-        tfm.translation += bd.velocity * time.delta_seconds();
+    let entities = query.iter().map(|(entity, ..)| entity).collect::<Vec<_>>();
+    let index_of = |entity: Entity| entities.iter().position(|&e| e == entity);
+
+    let mut bodies = Vec::with_capacity(entities.len());
+    let mut pinned = Vec::with_capacity(entities.len());
+    for (_, data, position, mut orbit_parent) in &mut query {
+        bodies.push(data.to_body(position.0));
+        let pinned_parent = orbit_parent.parent.and_then(index_of);
+        // the pinned parent may have been absorbed or deleted
+        if orbit_parent.pinned && pinned_parent.is_none() {
+            orbit_parent.pinned = false;
+        }
+        pinned.push(pinned_parent.filter(|_| orbit_parent.pinned));
+    }
+
+    let parents = orbit_parents(&bodies, &pinned);
+    for ((_, _, _, mut orbit_parent), parent) in query.iter_mut().zip(parents) {
+        let parent = parent.map(|index| entities[index]);
+        if !orbit_parent.pinned && orbit_parent.parent != parent {
+            orbit_parent.parent = parent;
+        }
     }
 }
 
@@ -81,7 +260,7 @@ fn radius_changed(
     mut query: Query<(&mut Transform, &CelestialBodyData), Changed<CelestialBodyData>>,
 ) {
     for (mut tfm, data) in &mut query {
-        tfm.scale = Vec3::ONE * data.radius
+        tfm.scale = Vec3::ONE * data.radius as f32
     }
 }
 
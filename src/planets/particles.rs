@@ -0,0 +1,483 @@
+use std::fmt;
+
+use bevy::{
+    asset::load_internal_asset,
+    core_pipeline::core_3d::Transparent3d,
+    ecs::{
+        query::QueryItem,
+        system::{lifetimeless::*, SystemParamItem},
+    },
+    math::DVec3,
+    pbr::{
+        MeshPipeline, MeshPipelineKey, RenderMeshInstances, SetMeshBindGroup, SetMeshViewBindGroup,
+    },
+    prelude::*,
+    render::{
+        extract_component::{ExtractComponent, ExtractComponentPlugin},
+        mesh::{GpuBufferInfo, MeshVertexBufferLayout},
+        render_asset::RenderAssets,
+        render_phase::{
+            AddRenderCommand, DrawFunctions, PhaseItem, RenderCommand, RenderCommandResult,
+            RenderPhase, SetItemPipeline, TrackedRenderPass,
+        },
+        render_resource::*,
+        renderer::RenderDevice,
+        view::{ExtractedView, NoFrustumCulling},
+        Render, RenderApp, RenderSet,
+    },
+    transform::TransformSystem,
+};
+use bytemuck::{Pod, Zeroable};
+use rand::Rng;
+
+use crate::camera::floating_origin::FloatingOrigin;
+use crate::simulation::Body;
+
+const PARTICLES_SHADER_HANDLE: Handle<Shader> = Handle::weak_from_u128(8354123270589734017);
+
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
+/// The shape of a new particle cloud around a body.
+pub enum CloudShape {
+    /// A flat ring in the body's equatorial plane.
+    #[default]
+    Ring,
+    /// A thick disk in the body's equatorial plane, uniformly filled.
+    Disk,
+    /// A spherical shell, the particles orbiting in random planes.
+    Sphere,
+}
+
+impl CloudShape {
+    /// Every shape, in the order they are shown in the UI.
+    pub const ALL: [CloudShape; 3] = [CloudShape::Ring, CloudShape::Disk, CloudShape::Sphere];
+}
+
+impl fmt::Display for CloudShape {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            CloudShape::Ring => "Ring",
+            CloudShape::Disk => "Disk",
+            CloudShape::Sphere => "Spherical cloud",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// How the particles of a new cloud are scattered around a body.
+pub struct CloudLayout {
+    /// The shape of the cloud.
+    pub shape: CloudShape,
+    /// The number of particles.
+    pub count: usize,
+    /// The distance from the body's center where the cloud starts.
+    pub inner_radius: f64,
+    /// The distance from the body's center where the cloud ends.
+    pub outer_radius: f64,
+    /// The thickness of a disk.
+    pub thickness: f64,
+}
+
+impl CloudLayout {
+    /// Draws the position and velocity of a particle relative to the body, on
+    /// a circular orbit around it given its gravitational parameter `mu`.
+    fn sample(&self, rng: &mut impl Rng, mu: f64) -> (DVec3, DVec3) {
+        let (inner, outer) = (
+            self.inner_radius.min(self.outer_radius),
+            self.inner_radius.max(self.outer_radius),
+        );
+        let angle = rng.gen_range(0.0..std::f64::consts::TAU);
+        let (position, normal) = match self.shape {
+            CloudShape::Ring => {
+                let radius = inner + rng.gen::<f64>() * (outer - inner);
+                (DVec3::new(angle.cos(), 0., angle.sin()) * radius, DVec3::Y)
+            }
+            CloudShape::Disk => {
+                // uniform surface density
+                let radius =
+                    (inner.powi(2) + rng.gen::<f64>() * (outer.powi(2) - inner.powi(2))).sqrt();
+                let height = (rng.gen::<f64>() - 0.5) * self.thickness;
+                (
+                    DVec3::new(angle.cos() * radius, height, angle.sin() * radius),
+                    DVec3::Y,
+                )
+            }
+            CloudShape::Sphere => {
+                // uniform volume density
+                let radius =
+                    (inner.powi(3) + rng.gen::<f64>() * (outer.powi(3) - inner.powi(3))).cbrt();
+                let direction = random_direction(rng);
+                let normal = direction
+                    .cross(random_direction(rng))
+                    .try_normalize()
+                    .unwrap_or_else(|| direction.any_orthonormal_vector());
+                (direction * radius, normal)
+            }
+        };
+
+        // prograde circular orbit around `normal`, at the speed given by the
+        // distance in the orbital plane
+        let in_plane = position - normal * position.dot(normal);
+        let distance = in_plane.length();
+        if distance == 0. || mu <= 0. {
+            return (position, DVec3::ZERO);
+        }
+        let velocity = normal.cross(in_plane / distance) * (mu / distance).sqrt();
+        (position, velocity)
+    }
+}
+
+/// A direction drawn uniformly on the unit sphere.
+fn random_direction(rng: &mut impl Rng) -> DVec3 {
+    let z = rng.gen_range(-1.0..=1.0_f64);
+    let angle = rng.gen_range(0.0..std::f64::consts::TAU);
+    let r = (1. - z * z).sqrt();
+    DVec3::new(r * angle.cos(), r * angle.sin(), z)
+}
+
+#[derive(Component, Debug, Clone, Default)]
+/// A cloud of massless particles, accelerated by the bodies without
+/// exerting any gravity themselves.
+///
+/// Particles are not entities: they are simulated along with the bodies and
+/// rendered as instances of a single mesh.
+pub struct ParticleCloud {
+    /// The particles' positions in the simulation.
+    pub positions: Vec<DVec3>,
+    /// The particles' velocities.
+    pub velocities: Vec<DVec3>,
+    /// The particles' color.
+    pub color: [f32; 3],
+    /// The particles' rendered radius.
+    pub size: f32,
+}
+
+impl ParticleCloud {
+    /// Scatters a new cloud around a body at `center` moving at `center_velocity`,
+    /// of gravitational parameter `mu`.
+    pub fn around(
+        layout: &CloudLayout,
+        center: DVec3,
+        center_velocity: DVec3,
+        mu: f64,
+        color: [f32; 3],
+        size: f32,
+    ) -> Self {
+        let mut rng = rand::thread_rng();
+        let (positions, velocities) = (0..layout.count)
+            .map(|_| {
+                let (position, velocity) = layout.sample(&mut rng, mu);
+                (center + position, center_velocity + velocity)
+            })
+            .unzip();
+        Self {
+            positions,
+            velocities,
+            color,
+            size,
+        }
+    }
+
+    /// Returns the physical state of the particles, for the simulation.
+    pub fn bodies(&self) -> impl Iterator<Item = Body> + '_ {
+        self.positions
+            .iter()
+            .zip(&self.velocities)
+            .map(|(&position, &velocity)| Body {
+                mass: 0.,
+                radius: 0.,
+                position,
+                velocity,
+                softening: None,
+                acceleration: DVec3::ZERO,
+            })
+    }
+}
+
+#[derive(Bundle)]
+/// A bundle of components for a `ParticleCloud`.
+pub struct ParticleCloudBundle {
+    /// The mesh drawn for each particle.
+    pub mesh: Handle<Mesh>,
+    /// The cloud's entity is never moved, the particles are placed by the shader.
+    pub spatial: SpatialBundle,
+    /// The particles.
+    pub cloud: ParticleCloud,
+    /// The particles as sent to the GPU.
+    pub instances: ParticleInstances,
+    /// The particles are spread well beyond the mesh's bounding box.
+    pub no_frustum_culling: NoFrustumCulling,
+}
+
+impl ParticleCloudBundle {
+    /// Constructs a new `ParticleCloudBundle`.
+    pub fn new(cloud: ParticleCloud, meshes: &mut Assets<Mesh>) -> Self {
+        Self {
+            mesh: meshes.add(Sphere::new(1.0).mesh().ico(1).unwrap()),
+            spatial: SpatialBundle::INHERITED_IDENTITY,
+            cloud,
+            instances: ParticleInstances::default(),
+            no_frustum_culling: NoFrustumCulling,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Pod, Zeroable)]
+#[repr(C)]
+/// A particle as sent to the GPU.
+struct ParticleInstance {
+    /// The rendered translation of the particle.
+    position: Vec3,
+    /// The rendered radius of the particle.
+    scale: f32,
+    /// The linear color of the particle.
+    color: [f32; 4],
+}
+
+#[derive(Component, Clone, Default)]
+/// The particles of a `ParticleCloud` relative to the `FloatingOrigin`,
+/// updated every frame.
+pub struct ParticleInstances(Vec<ParticleInstance>);
+
+impl ExtractComponent for ParticleInstances {
+    type QueryData = &'static ParticleInstances;
+    type QueryFilter = ();
+    type Out = Self;
+
+    fn extract_component(item: QueryItem<'_, Self::QueryData>) -> Option<Self> {
+        Some(item.clone())
+    }
+}
+
+/// Plugin rendering the particle clouds, all the particles of a cloud in a
+/// single instanced draw call.
+pub struct ParticlePlugin;
+
+impl Plugin for ParticlePlugin {
+    fn build(&self, app: &mut App) {
+        load_internal_asset!(
+            app,
+            PARTICLES_SHADER_HANDLE,
+            "particles.wgsl",
+            Shader::from_wgsl
+        );
+        app.add_plugins(ExtractComponentPlugin::<ParticleInstances>::default())
+            .add_systems(
+                PostUpdate,
+                update_particle_instances.after(TransformSystem::TransformPropagate),
+            );
+
+        let Ok(render_app) = app.get_sub_app_mut(RenderApp) else {
+            return;
+        };
+        render_app
+            .add_render_command::<Transparent3d, DrawParticles>()
+            .init_resource::<SpecializedMeshPipelines<ParticlePipeline>>()
+            .add_systems(
+                Render,
+                (
+                    queue_particles.in_set(RenderSet::QueueMeshes),
+                    prepare_particle_buffers.in_set(RenderSet::PrepareResources),
+                ),
+            );
+    }
+
+    fn finish(&self, app: &mut App) {
+        if let Ok(render_app) = app.get_sub_app_mut(RenderApp) {
+            render_app.init_resource::<ParticlePipeline>();
+        }
+    }
+}
+
+/// Places the particles relative to the floating origin.
+fn update_particle_instances(
+    origin: Res<FloatingOrigin>,
+    mut query: Query<(&ParticleCloud, &mut ParticleInstances)>,
+) {
+    for (cloud, mut instances) in &mut query {
+        let color = Color::rgb_from_array(cloud.color).as_linear_rgba_f32();
+        instances.0.clear();
+        instances
+            .0
+            .extend(cloud.positions.iter().map(|&position| ParticleInstance {
+                position: origin.to_render(position),
+                scale: cloud.size,
+                color,
+            }));
+    }
+}
+
+/// Queues the non-empty clouds in the transparent phase of every view.
+#[allow(clippy::too_many_arguments)]
+fn queue_particles(
+    transparent_3d_draw_functions: Res<DrawFunctions<Transparent3d>>,
+    particle_pipeline: Res<ParticlePipeline>,
+    msaa: Res<Msaa>,
+    mut pipelines: ResMut<SpecializedMeshPipelines<ParticlePipeline>>,
+    pipeline_cache: Res<PipelineCache>,
+    meshes: Res<RenderAssets<Mesh>>,
+    render_mesh_instances: Res<RenderMeshInstances>,
+    clouds: Query<(Entity, &ParticleInstances)>,
+    mut views: Query<(&ExtractedView, &mut RenderPhase<Transparent3d>)>,
+) {
+    let draw_particles = transparent_3d_draw_functions.read().id::<DrawParticles>();
+    let msaa_key = MeshPipelineKey::from_msaa_samples(msaa.samples());
+
+    for (view, mut transparent_phase) in &mut views {
+        let view_key = msaa_key | MeshPipelineKey::from_hdr(view.hdr);
+        let rangefinder = view.rangefinder3d();
+        for (entity, instances) in &clouds {
+            if instances.0.is_empty() {
+                continue;
+            }
+            let Some(mesh_instance) = render_mesh_instances.get(&entity) else {
+                continue;
+            };
+            let Some(mesh) = meshes.get(mesh_instance.mesh_asset_id) else {
+                continue;
+            };
+            let key = view_key | MeshPipelineKey::from_primitive_topology(mesh.primitive_topology);
+            let pipeline = pipelines
+                .specialize(&pipeline_cache, &particle_pipeline, key, &mesh.layout)
+                .unwrap();
+            transparent_phase.add(Transparent3d {
+                entity,
+                pipeline,
+                draw_function: draw_particles,
+                distance: rangefinder
+                    .distance_translation(&mesh_instance.transforms.transform.translation),
+                batch_range: 0..1,
+                dynamic_offset: None,
+            });
+        }
+    }
+}
+
+#[derive(Component)]
+/// The GPU buffer holding the particles of a cloud.
+struct ParticleBuffer {
+    buffer: Buffer,
+    length: usize,
+}
+
+/// Uploads the particles of every non-empty cloud.
+fn prepare_particle_buffers(
+    mut commands: Commands,
+    query: Query<(Entity, &ParticleInstances)>,
+    render_device: Res<RenderDevice>,
+) {
+    for (entity, instances) in &query {
+        if instances.0.is_empty() {
+            continue;
+        }
+        let buffer = render_device.create_buffer_with_data(&BufferInitDescriptor {
+            label: Some("particle instance buffer"),
+            contents: bytemuck::cast_slice(instances.0.as_slice()),
+            usage: BufferUsages::VERTEX | BufferUsages::COPY_DST,
+        });
+        commands.entity(entity).insert(ParticleBuffer {
+            buffer,
+            length: instances.0.len(),
+        });
+    }
+}
+
+#[derive(Resource)]
+/// The mesh pipeline, with the particles' shader and instance buffer.
+struct ParticlePipeline {
+    mesh_pipeline: MeshPipeline,
+}
+
+impl FromWorld for ParticlePipeline {
+    fn from_world(world: &mut World) -> Self {
+        Self {
+            mesh_pipeline: world.resource::<MeshPipeline>().clone(),
+        }
+    }
+}
+
+impl SpecializedMeshPipeline for ParticlePipeline {
+    type Key = MeshPipelineKey;
+
+    fn specialize(
+        &self,
+        key: Self::Key,
+        layout: &MeshVertexBufferLayout,
+    ) -> Result<RenderPipelineDescriptor, SpecializedMeshPipelineError> {
+        let mut descriptor = self.mesh_pipeline.specialize(key, layout)?;
+
+        descriptor.vertex.shader = PARTICLES_SHADER_HANDLE;
+        descriptor.vertex.buffers.push(VertexBufferLayout {
+            array_stride: std::mem::size_of::<ParticleInstance>() as u64,
+            step_mode: VertexStepMode::Instance,
+            attributes: vec![
+                // locations 0 to 2 hold the mesh's positions, normals and uvs
+                VertexAttribute {
+                    format: VertexFormat::Float32x4,
+                    offset: 0,
+                    shader_location: 3,
+                },
+                VertexAttribute {
+                    format: VertexFormat::Float32x4,
+                    offset: VertexFormat::Float32x4.size(),
+                    shader_location: 4,
+                },
+            ],
+        });
+        descriptor.fragment.as_mut().unwrap().shader = PARTICLES_SHADER_HANDLE;
+        Ok(descriptor)
+    }
+}
+
+type DrawParticles = (
+    SetItemPipeline,
+    SetMeshViewBindGroup<0>,
+    SetMeshBindGroup<1>,
+    DrawParticlesInstanced,
+);
+
+/// Draws the mesh of a cloud once per particle.
+struct DrawParticlesInstanced;
+
+impl<P: PhaseItem> RenderCommand<P> for DrawParticlesInstanced {
+    type Param = (SRes<RenderAssets<Mesh>>, SRes<RenderMeshInstances>);
+    type ViewQuery = ();
+    type ItemQuery = Read<ParticleBuffer>;
+
+    #[inline]
+    fn render<'w>(
+        item: &P,
+        _view: (),
+        particle_buffer: Option<&'w ParticleBuffer>,
+        (meshes, render_mesh_instances): SystemParamItem<'w, '_, Self::Param>,
+        pass: &mut TrackedRenderPass<'w>,
+    ) -> RenderCommandResult {
+        let Some(mesh_instance) = render_mesh_instances.get(&item.entity()) else {
+            return RenderCommandResult::Failure;
+        };
+        let Some(gpu_mesh) = meshes.into_inner().get(mesh_instance.mesh_asset_id) else {
+            return RenderCommandResult::Failure;
+        };
+        let Some(particle_buffer) = particle_buffer else {
+            return RenderCommandResult::Failure;
+        };
+
+        pass.set_vertex_buffer(0, gpu_mesh.vertex_buffer.slice(..));
+        pass.set_vertex_buffer(1, particle_buffer.buffer.slice(..));
+
+        match &gpu_mesh.buffer_info {
+            GpuBufferInfo::Indexed {
+                buffer,
+                index_format,
+                count,
+            } => {
+                pass.set_index_buffer(buffer.slice(..), 0, *index_format);
+                pass.draw_indexed(0..*count, 0, 0..particle_buffer.length as u32);
+            }
+            GpuBufferInfo::NonIndexed => {
+                pass.draw(0..gpu_mesh.vertex_count, 0..particle_buffer.length as u32);
+            }
+        }
+        RenderCommandResult::Success
+    }
+}
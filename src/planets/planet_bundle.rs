@@ -1,6 +1,13 @@
-use bevy::prelude::*;
+use std::{
+    fmt,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use bevy::{math::DVec3, prelude::*};
 use serde::{Deserialize, Serialize};
 
+use crate::simulation::Body;
+
 #[derive(Bundle)]
 /// A bundle of components for a `CelestialBody`.
 pub struct CelestialBodyBundle {
@@ -8,16 +15,174 @@ pub struct CelestialBodyBundle {
     pub pbr: PbrBundle,
     /// The body's data.
     pub body_data: CelestialBodyData,
+    /// The body's position in the simulation.
+    pub position: Position,
+    /// The body's current acceleration.
+    pub acceleration: Acceleration,
+    /// The body the body orbits.
+    pub orbit_parent: OrbitParent,
+    /// When the body was spawned.
+    pub spawn_order: SpawnOrder,
+}
+
+/// Spawns a body at `position`, with its mesh, its material and, for stars, its light.
+pub fn spawn_body(
+    commands: &mut Commands,
+    meshes: &mut Assets<Mesh>,
+    materials: &mut Assets<StandardMaterial>,
+    body_data: CelestialBodyData,
+    position: DVec3,
+    orbit_parent: OrbitParent,
+) -> Entity {
+    let material = materials.add(body_data.material());
+    let point_light = body_data.point_light();
+    let mut entity_command = commands.spawn(CelestialBodyBundle {
+        pbr: PbrBundle {
+            mesh: meshes.add(Sphere::new(1.0).mesh().ico(5).unwrap()),
+            material,
+            transform: Transform::from_scale(Vec3::ONE * body_data.radius as f32),
+            ..Default::default()
+        },
+        body_data,
+        position: Position(position),
+        acceleration: Acceleration::default(),
+        orbit_parent,
+        spawn_order: SpawnOrder::next(),
+    });
+    if let Some(point_light) = point_light {
+        entity_command.with_children(|p| {
+            p.spawn(point_light);
+        });
+    }
+    entity_command.id()
+}
+
+#[derive(Component, Debug, Default, Clone, Copy)]
+/// The authoritative position of a body, in double precision.
+///
+/// The body's `Transform` is derived from it relative to the `FloatingOrigin`
+/// and must not be edited directly.
+pub struct Position(pub DVec3);
+
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+/// The order in which bodies were spawned.
+///
+/// Entities can't be ordered by their ids: a freed id is reused, with a newer
+/// generation, by the next spawned entity.
+pub struct SpawnOrder(u64);
+
+impl SpawnOrder {
+    fn next() -> Self {
+        static NEXT: AtomicU64 = AtomicU64::new(0);
+        Self(NEXT.fetch_add(1, Ordering::Relaxed))
+    }
+}
+
+#[derive(Component, Debug, Default, Clone, Copy)]
+/// The gravitational acceleration of a body, updated by each simulation step.
+pub struct Acceleration(pub DVec3);
+
+#[derive(Component, Debug, Default, Clone, Copy, PartialEq)]
+/// The body a body orbits, updated from the Hill spheres unless pinned.
+pub struct OrbitParent {
+    /// The parent body, `None` for the bodies orbiting nothing.
+    pub parent: Option<Entity>,
+    /// Whether the parent was chosen by the user.
+    pub pinned: bool,
 }
 
-#[derive(Component, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 /// Different types of `CelestialBodies`
 pub enum CelestialBodyType {
     Planet,
+    /// Lights up the scene.
     Star,
+    Moon,
+    Asteroid,
+    /// Trails a tail pointing away from the nearest star.
+    Comet,
+    /// A dark sphere surrounded by a glowing accretion disk of the body's color.
+    BlackHole,
+    DwarfPlanet,
+    /// Massless: feels the gravity of the other bodies without exerting any.
+    TestParticle,
 }
 
-#[derive(Component, Debug, Clone, Deserialize, Serialize)]
+impl CelestialBodyType {
+    /// Every body type, in the order they are shown in the UI.
+    pub const ALL: [CelestialBodyType; 8] = [
+        CelestialBodyType::Planet,
+        CelestialBodyType::Star,
+        CelestialBodyType::Moon,
+        CelestialBodyType::Asteroid,
+        CelestialBodyType::Comet,
+        CelestialBodyType::BlackHole,
+        CelestialBodyType::DwarfPlanet,
+        CelestialBodyType::TestParticle,
+    ];
+
+    /// The default emissive and light factors of the type.
+    fn emissive_and_light(self) -> (f32, f32) {
+        match self {
+            CelestialBodyType::Star => (18.0, 1_000_000_000.0),
+            // the coma of a comet glows faintly
+            CelestialBodyType::Comet => (2.0, 0.0),
+            CelestialBodyType::Planet
+            | CelestialBodyType::Moon
+            | CelestialBodyType::Asteroid
+            | CelestialBodyType::BlackHole
+            | CelestialBodyType::DwarfPlanet
+            | CelestialBodyType::TestParticle => (0.0, 0.0),
+        }
+    }
+
+    /// The default mass and radius of the type, in the toy unit system.
+    pub fn default_mass_and_radius(self) -> (f64, f64) {
+        match self {
+            CelestialBodyType::Star => (1000., 10.),
+            CelestialBodyType::Planet => (10., 3.),
+            CelestialBodyType::Moon => (0.5, 1.),
+            CelestialBodyType::DwarfPlanet => (0.2, 0.8),
+            CelestialBodyType::Asteroid | CelestialBodyType::Comet => (0.01, 0.3),
+            // far heavier than a star, yet smaller than a planet
+            CelestialBodyType::BlackHole => (10_000., 2.),
+            CelestialBodyType::TestParticle => (0., 0.2),
+        }
+    }
+
+    /// Converts the mass and radius of a body of this type to the ones a body
+    /// of type `to` would have on the same scale, whatever the unit system.
+    ///
+    /// Test particles have no mass to convert from, and get none.
+    pub fn rescale(self, to: CelestialBodyType, mass: f64, radius: f64) -> (f64, f64) {
+        let (from_mass, from_radius) = self.default_mass_and_radius();
+        let (to_mass, to_radius) = to.default_mass_and_radius();
+        let mass = if from_mass > 0. {
+            mass * to_mass / from_mass
+        } else {
+            0.
+        };
+        (mass, radius * to_radius / from_radius)
+    }
+}
+
+impl fmt::Display for CelestialBodyType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            CelestialBodyType::Planet => "Planet",
+            CelestialBodyType::Star => "Star",
+            CelestialBodyType::Moon => "Moon",
+            CelestialBodyType::Asteroid => "Asteroid",
+            CelestialBodyType::Comet => "Comet",
+            CelestialBodyType::BlackHole => "Black hole",
+            CelestialBodyType::DwarfPlanet => "Dwarf planet",
+            CelestialBodyType::TestParticle => "Test particle",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+#[derive(Component, Debug, Clone, PartialEq, Deserialize, Serialize)]
 /// The data for a Body
 pub struct CelestialBodyData {
     /// The body's name, acts as an identifier.
@@ -25,61 +190,177 @@ pub struct CelestialBodyData {
     /// The body's type
     pub body_type: CelestialBodyType,
     /// The body's mass.
-    pub mass: f32,
+    pub mass: f64,
     /// The body's radius.
-    pub radius: f32,
+    pub radius: f64,
     /// The body's initial velocity.
-    pub initial_velocity: Vec3,
+    pub initial_velocity: DVec3,
     /// The body's current velocity.
-    pub velocity: Vec3,
+    pub velocity: DVec3,
     /// The body's color.
     pub color: [f32; 3],
     /// How emissive the material is
     pub emissive_factor: f32,
     /// How much light is sent to other bodies (onyl if  it's a star)
     pub light_factor: f32,
+    /// Overrides the global softening length for this body.
+    #[serde(default)]
+    pub softening: Option<f64>,
+    /// The mass the body had before becoming a test particle, as the mass of a
+    /// planet of the same scale, restored when it stops being one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    previous_mass: Option<f64>,
 }
 
 impl CelestialBodyData {
     /// Constructs a new `CelestialBodyData`.
+    ///
+    /// Test particles are massless whatever `mass` is.
     pub fn new(
         name: String,
         body_type: CelestialBodyType,
-        mass: f32,
-        radius: f32,
-        initial_velocity: Vec3,
+        mass: f64,
+        radius: f64,
+        initial_velocity: DVec3,
         color: Color,
     ) -> Self {
-        let (emissive, light) = match body_type {
-            CelestialBodyType::Planet => (0.0, 0.0),
-            CelestialBodyType::Star => (18.0, 1_000_000_000.0),
-        };
+        let (emissive, light) = body_type.emissive_and_light();
         Self {
             name,
             body_type,
-            mass,
+            mass: if body_type == CelestialBodyType::TestParticle {
+                0.
+            } else {
+                mass
+            },
             radius,
             initial_velocity,
             velocity: initial_velocity,
             color: color.rgb_linear_to_vec3().to_array(),
             emissive_factor: emissive,
             light_factor: light,
+            softening: None,
+            previous_mass: None,
         }
     }
 
-    /// Compute de velocity contribution from another body for a given time step.
-    pub fn compute_velocity(
-        &self,
-        position_1: Vec3,
-        position_2: Vec3,
-        mass_2: f32,
-        g: f32,
-        delta_seconds: f32,
-    ) -> Vec3 {
-        let squared_dist = position_1.distance_squared(position_2);
-        let mass = self.mass;
-        let force = (position_2 - position_1).normalize() * g * mass * mass_2 / squared_dist;
-
-        delta_seconds * force / mass
+    /// Changes the type of the body, resetting its emissive and light factors
+    /// to the new type's defaults and rescaling its mass and radius to the
+    /// ones of the new type. Test particles are massless, the mass the body had
+    /// before being given back when it stops being one.
+    pub fn set_body_type(&mut self, body_type: CelestialBodyType) {
+        if body_type == self.body_type {
+            return;
+        }
+        (self.emissive_factor, self.light_factor) = body_type.emissive_and_light();
+        let (mut mass, radius) = self.body_type.rescale(body_type, self.mass, self.radius);
+        if body_type == CelestialBodyType::TestParticle {
+            // kept on the scale of a planet, as the particle may become any type
+            let (planet_mass, _) =
+                self.body_type
+                    .rescale(CelestialBodyType::Planet, self.mass, self.radius);
+            self.previous_mass = Some(planet_mass);
+        } else if let Some(planet_mass) = self.previous_mass.take() {
+            (mass, _) = CelestialBodyType::Planet.rescale(body_type, planet_mass, radius);
+        }
+        self.mass = mass;
+        self.radius = radius;
+        self.body_type = body_type;
+    }
+
+    /// The material the body is rendered with.
+    pub fn material(&self) -> StandardMaterial {
+        let color = Color::rgb_from_array(self.color);
+        match self.body_type {
+            // the color of a black hole is the one of its accretion disk
+            CelestialBodyType::BlackHole => StandardMaterial {
+                base_color: Color::BLACK,
+                perceptual_roughness: 1.0,
+                reflectance: 0.0,
+                ..Default::default()
+            },
+            _ => StandardMaterial {
+                base_color: color,
+                emissive: color * self.emissive_factor,
+                ..Default::default()
+            },
+        }
+    }
+
+    /// The light the body sheds on the others, only for stars.
+    pub fn point_light(&self) -> Option<PointLightBundle> {
+        (self.body_type == CelestialBodyType::Star).then(|| PointLightBundle {
+            point_light: PointLight {
+                color: Color::WHITE,
+                intensity: self.light_factor,
+                range: 1000.0,
+                radius: self.radius as f32,
+                ..default()
+            },
+            ..default()
+        })
+    }
+
+    /// Returns the physical state of the body at `position`, for the simulation.
+    pub fn to_body(&self, position: DVec3) -> Body {
+        Body {
+            mass: self.mass,
+            radius: self.radius,
+            position,
+            velocity: self.velocity,
+            softening: self.softening,
+            acceleration: DVec3::ZERO,
+        }
+    }
+
+    /// Blends the color of an absorbed body into this one, weighted by their masses.
+    pub fn blend_color(&mut self, other_color: [f32; 3], own_mass: f64, other_mass: f64) {
+        let total_mass = own_mass + other_mass;
+        if total_mass <= 0. {
+            return;
+        }
+        for (own, other) in self.color.iter_mut().zip(other_color) {
+            *own = ((*own as f64 * own_mass + other as f64 * other_mass) / total_mass) as f32;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_particles_get_their_mass_back() {
+        let mut body = CelestialBodyData::new(
+            String::from("Moon"),
+            CelestialBodyType::Moon,
+            7.,
+            1.,
+            DVec3::ZERO,
+            Color::WHITE,
+        );
+        body.set_body_type(CelestialBodyType::TestParticle);
+        assert_eq!(body.mass, 0.);
+        body.set_body_type(CelestialBodyType::TestParticle);
+        body.set_body_type(CelestialBodyType::Moon);
+        assert_eq!((body.mass, body.radius), (7., 1.));
+    }
+
+    #[test]
+    fn changing_type_rescales_the_body() {
+        let mut body = CelestialBodyData::new(
+            String::from("Planet"),
+            CelestialBodyType::Planet,
+            20.,
+            6.,
+            DVec3::ZERO,
+            Color::WHITE,
+        );
+        body.set_body_type(CelestialBodyType::Star);
+        assert_eq!((body.mass, body.radius), (2000., 20.));
+        body.set_body_type(CelestialBodyType::TestParticle);
+        assert_eq!((body.mass, body.radius), (0., 0.4));
+        body.set_body_type(CelestialBodyType::BlackHole);
+        assert_eq!((body.mass, body.radius), (20_000., 4.));
     }
 }
@@ -0,0 +1,743 @@
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::{fmt, fs, io};
+
+use bevy::ecs::system::Resource;
+use bevy::math::{DVec3, Quat, Vec3};
+use bevy::transform::components::Transform;
+use serde::{Deserialize, Serialize};
+use serde_json::{error::Category, Value};
+
+use crate::planets::planet_bundle::CelestialBodyData;
+use crate::simulation::{
+    collisions::{retain_survivors, Collision},
+    integrators::IntegratorKind,
+    physics_config::PhysicsConfig,
+    Simulation,
+};
+use crate::ui::AppConfig;
+
+pub mod presets;
+
+/// The version of the scene files written by this version of the application.
+pub const SCENE_VERSION: u64 = 1;
+
+/// Bevy's default `FixedUpdate` timestep, the length of a simulation step.
+pub const DEFAULT_DT: f64 = 1. / 64.;
+
+/// Upgrades the content of a scene file by one version, the migration at
+/// index `n` turning version `n` into version `n + 1`.
+type Migration = fn(&mut serde_json::Map<String, Value>) -> Result<(), SceneError>;
+
+/// The migrations from every previous version to the current one.
+const MIGRATIONS: [Migration; SCENE_VERSION as usize] = [migrate_unversioned];
+
+/// Serializes a default value inserted by a migration.
+fn to_value<T: Serialize>(value: T) -> Result<Value, SceneError> {
+    Ok(serde_json::to_value(value)?)
+}
+
+/// Upgrades the unversioned scenes, which only stored the camera's position
+/// and, for the latest ones, the physics settings.
+fn migrate_unversioned(scene: &mut serde_json::Map<String, Value>) -> Result<(), SceneError> {
+    let camera = match scene.remove("camera_position") {
+        Some(position) => CameraPose {
+            position: serde_json::from_value(position)?,
+            ..Default::default()
+        },
+        None => CameraPose::default(),
+    };
+    scene.insert(String::from("camera"), to_value(camera)?);
+    if !scene.contains_key("physics") {
+        scene.insert(String::from("physics"), to_value(PhysicsConfig::default())?);
+    }
+    scene.insert(
+        String::from("settings"),
+        to_value(SimulationSettings::default())?,
+    );
+    scene.insert(String::from("app_config"), to_value(AppConfig::default())?);
+    scene.insert(String::from("elapsed"), Value::from(0.));
+    Ok(())
+}
+
+#[derive(Debug)]
+/// Why a scene could not be parsed or serialized.
+pub enum SceneError {
+    /// The file is not valid JSON, or does not describe a scene.
+    Json(serde_json::Error),
+    /// The file is valid JSON but not an object.
+    NotAnObject,
+    /// The version of the format is not a positive integer.
+    InvalidVersion(Value),
+    /// The scene was saved by a newer version of the application.
+    UnsupportedVersion(u64),
+}
+
+impl SceneError {
+    /// The line and column of the error in the file, starting at 1, when known.
+    pub fn location(&self) -> Option<(usize, usize)> {
+        match self {
+            SceneError::Json(e) if e.line() > 0 => Some((e.line(), e.column())),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for SceneError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SceneError::Json(e) => {
+                let problem = match e.classify() {
+                    Category::Io => "The scene could not be read",
+                    Category::Syntax => "Invalid JSON",
+                    Category::Data => "The JSON does not describe a scene",
+                    Category::Eof => "Unexpected end of file",
+                };
+                write!(f, "{}", problem)?;
+                if let Some((line, column)) = self.location() {
+                    write!(f, " at line {}, column {}", line, column)?;
+                }
+                Ok(())
+            }
+            SceneError::NotAnObject => write!(f, "A scene must be a JSON object"),
+            SceneError::InvalidVersion(version) => {
+                write!(f, "Invalid scene version {}", version)
+            }
+            SceneError::UnsupportedVersion(version) => write!(
+                f,
+                "The scene was saved with version {} of the format, only versions up to {} are supported",
+                version, SCENE_VERSION
+            ),
+        }
+    }
+}
+
+impl std::error::Error for SceneError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            SceneError::Json(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<serde_json::Error> for SceneError {
+    fn from(e: serde_json::Error) -> Self {
+        SceneError::Json(e)
+    }
+}
+
+#[derive(Debug)]
+/// Why a scene file could not be opened or saved.
+pub enum SceneIoError {
+    /// The file could not be read.
+    Read(PathBuf, io::Error),
+    /// The file does not contain a valid scene.
+    Parse(PathBuf, SceneError),
+    /// The scene could not be serialized.
+    Serialize(PathBuf, SceneError),
+    /// The file could not be written.
+    Write(PathBuf, io::Error),
+}
+
+impl SceneIoError {
+    /// The file being read or written.
+    pub fn path(&self) -> &Path {
+        match self {
+            SceneIoError::Read(path, _)
+            | SceneIoError::Parse(path, _)
+            | SceneIoError::Serialize(path, _)
+            | SceneIoError::Write(path, _) => path,
+        }
+    }
+
+    /// What went wrong, without the file.
+    pub fn cause(&self) -> String {
+        match self {
+            SceneIoError::Read(_, e) | SceneIoError::Write(_, e) => e.to_string(),
+            SceneIoError::Parse(_, e) | SceneIoError::Serialize(_, e) => e.to_string(),
+        }
+    }
+
+    /// The lines describing the error in a notification.
+    pub fn details(&self) -> Vec<String> {
+        let mut details = vec![self.path().display().to_string(), self.cause()];
+        // serde_json's explanation, such as the missing or invalid field
+        if let SceneIoError::Parse(_, SceneError::Json(e))
+        | SceneIoError::Serialize(_, SceneError::Json(e)) = self
+        {
+            details.push(e.to_string());
+        }
+        details
+    }
+}
+
+impl fmt::Display for SceneIoError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let action = match self {
+            SceneIoError::Read(..) => "reading",
+            SceneIoError::Parse(..) => "deserializing data from",
+            SceneIoError::Serialize(..) => "serializing the scene for",
+            SceneIoError::Write(..) => "writing",
+        };
+        write!(
+            f,
+            "Error while {} {} : {}",
+            action,
+            self.path().display(),
+            self.cause()
+        )
+    }
+}
+
+impl std::error::Error for SceneIoError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            SceneIoError::Read(_, e) | SceneIoError::Write(_, e) => Some(e),
+            SceneIoError::Parse(_, e) | SceneIoError::Serialize(_, e) => Some(e),
+        }
+    }
+}
+
+/// Reads and parses a scene file.
+pub fn read_scene(path: &Path) -> Result<SceneFile, SceneIoError> {
+    let data = fs::read_to_string(path).map_err(|e| SceneIoError::Read(path.to_path_buf(), e))?;
+    SceneFile::from_json(&data).map_err(|e| SceneIoError::Parse(path.to_path_buf(), e))
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// Where a scene is loaded from.
+pub enum SceneSource {
+    File(PathBuf),
+    /// One of the scenes shipped with the application.
+    Preset(presets::Preset),
+}
+
+impl SceneSource {
+    /// Reads the scene.
+    pub fn read(&self) -> Result<SceneFile, SceneIoError> {
+        match self {
+            SceneSource::File(path) => read_scene(path),
+            SceneSource::Preset(preset) => Ok(preset.scene()),
+        }
+    }
+}
+
+impl fmt::Display for SceneSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SceneSource::File(path) => write!(f, "{}", path.display()),
+            SceneSource::Preset(preset) => write!(f, "the {} preset", preset),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+/// A body as stored in a scene file.
+pub struct CelestialBodyRelevantData {
+    pub body_data: CelestialBodyData,
+    pub position: DVec3,
+    /// The index of the body this one was pinned to orbit, if any.
+    #[serde(default)]
+    pub pinned_parent: Option<usize>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+/// The position and orientation of the camera.
+pub struct CameraPose {
+    /// The position of the camera in the simulation.
+    pub position: DVec3,
+    pub rotation: Quat,
+}
+
+impl CameraPose {
+    /// Looks down on the origin from `height` above it.
+    pub fn above(height: f64) -> Self {
+        let transform =
+            Transform::from_xyz(0.0, height as f32, 0.0).looking_at(Vec3::ZERO, -Vec3::Z);
+        Self {
+            position: transform.translation.as_dvec3(),
+            rotation: transform.rotation,
+        }
+    }
+}
+
+impl Default for CameraPose {
+    /// The pose of the camera when the application starts, looking down on the scene.
+    fn default() -> Self {
+        Self::above(500.0)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+/// How the simulation is advanced.
+pub struct SimulationSettings {
+    pub integrator: IntegratorKind,
+    /// The length of a simulation step, before time warp.
+    pub dt: f64,
+    /// How many simulated seconds pass per real second.
+    pub time_warp: f32,
+}
+
+impl Default for SimulationSettings {
+    fn default() -> Self {
+        Self {
+            integrator: IntegratorKind::default(),
+            dt: DEFAULT_DT,
+            time_warp: 1.,
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+/// What to do with an imported body named like a body of the current scene.
+pub enum NameCollision {
+    /// Appends a number to the name of the imported body.
+    #[default]
+    Rename,
+    /// Leaves the imported body out.
+    Skip,
+    /// Keeps both bodies with the same name.
+    Keep,
+}
+
+impl NameCollision {
+    /// Every way of handling collisions, in the order they are shown in the UI.
+    pub const ALL: [NameCollision; 3] = [
+        NameCollision::Rename,
+        NameCollision::Skip,
+        NameCollision::Keep,
+    ];
+}
+
+impl fmt::Display for NameCollision {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            NameCollision::Rename => "Rename",
+            NameCollision::Skip => "Skip",
+            NameCollision::Keep => "Keep both",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+#[derive(Resource, Debug, Default, Clone, Copy, PartialEq)]
+/// How the bodies of a scene are merged into the current one.
+pub struct ImportOptions {
+    /// Added to the position of every imported body.
+    pub offset: DVec3,
+    /// Added to the velocity of every imported body.
+    pub velocity_offset: DVec3,
+    pub name_collision: NameCollision,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+/// The content of a scene file.
+pub struct SceneFile {
+    /// The version of the format, see `SCENE_VERSION`.
+    pub version: u64,
+    pub celestial_bodies: Vec<CelestialBodyRelevantData>,
+    pub camera: CameraPose,
+    pub physics: PhysicsConfig,
+    pub settings: SimulationSettings,
+    pub app_config: AppConfig,
+    /// The simulated time elapsed when the scene was saved.
+    pub elapsed: f64,
+}
+
+impl Default for SceneFile {
+    fn default() -> Self {
+        Self {
+            version: SCENE_VERSION,
+            celestial_bodies: Vec::new(),
+            camera: CameraPose::default(),
+            physics: PhysicsConfig::default(),
+            settings: SimulationSettings::default(),
+            app_config: AppConfig::default(),
+            elapsed: 0.,
+        }
+    }
+}
+
+impl SceneFile {
+    /// Parses a scene file, upgrading it if it was written by a previous version.
+    pub fn from_json(json: &str) -> Result<Self, SceneError> {
+        let mut value = serde_json::from_str::<Value>(json)?;
+        let scene = value.as_object_mut().ok_or(SceneError::NotAnObject)?;
+        let version = match scene.get("version") {
+            None => 0,
+            Some(version) => version
+                .as_u64()
+                .ok_or_else(|| SceneError::InvalidVersion(version.clone()))?,
+        };
+        if version > SCENE_VERSION {
+            return Err(SceneError::UnsupportedVersion(version));
+        }
+        if version == SCENE_VERSION {
+            // parsed from the text again so that errors point to where they are
+            return Ok(serde_json::from_str(json)?);
+        }
+        for migration in &MIGRATIONS[version as usize..] {
+            migration(scene)?;
+        }
+        scene.insert(String::from("version"), Value::from(SCENE_VERSION));
+        Ok(serde_json::from_value(value)?)
+    }
+
+    /// Serializes the scene.
+    pub fn to_json(&self) -> Result<String, SceneError> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+
+    /// The bodies of the scene, moved and renamed to be merged into a scene
+    /// whose bodies are named `existing`.
+    pub fn imported_bodies(
+        self,
+        options: &ImportOptions,
+        existing: &HashSet<String>,
+    ) -> Vec<CelestialBodyRelevantData> {
+        let mut taken = existing.clone();
+        let mut kept = Vec::new();
+        let mut bodies = Vec::new();
+        for (index, mut body) in self.celestial_bodies.into_iter().enumerate() {
+            if existing.contains(&body.body_data.name) {
+                match options.name_collision {
+                    NameCollision::Skip => continue,
+                    NameCollision::Rename => {
+                        body.body_data.name = (2..)
+                            .map(|n| format!("{} ({})", body.body_data.name, n))
+                            .find(|name| !taken.contains(name))
+                            .unwrap();
+                    }
+                    NameCollision::Keep => (),
+                }
+            }
+            taken.insert(body.body_data.name.clone());
+            body.position += options.offset;
+            body.body_data.velocity += options.velocity_offset;
+            body.body_data.initial_velocity += options.velocity_offset;
+            kept.push(index);
+            bodies.push(body);
+        }
+        // the bodies pinned to a skipped body are no longer pinned
+        for body in &mut bodies {
+            body.pinned_parent = body
+                .pinned_parent
+                .and_then(|parent| kept.binary_search(&parent).ok());
+        }
+        bodies
+    }
+
+    /// Builds a `Simulation` from the bodies of the scene.
+    pub fn to_simulation(&self, integrator: IntegratorKind) -> Simulation {
+        let mut simulation = Simulation::new(self.physics, integrator);
+        simulation.elapsed = self.elapsed;
+        simulation.bodies = self
+            .celestial_bodies
+            .iter()
+            .map(|body| body.body_data.to_body(body.position))
+            .collect();
+        simulation
+    }
+
+    /// Copies the state of the simulated bodies back into the scene.
+    pub fn update_from_simulation(&mut self, simulation: &Simulation) {
+        self.elapsed = simulation.elapsed;
+        for (body, simulated) in self.celestial_bodies.iter_mut().zip(&simulation.bodies) {
+            body.position = simulated.position;
+            body.body_data.velocity = simulated.velocity;
+            body.body_data.mass = simulated.mass;
+            body.body_data.radius = simulated.radius;
+        }
+    }
+
+    /// Removes the bodies absorbed during a simulation step and blends their
+    /// colors into the bodies that absorbed them.
+    pub fn apply_collisions(&mut self, collisions: &[Collision]) {
+        for collision in collisions.iter().filter(|c| c.absorbed().is_some()) {
+            let absorbed_color = self.celestial_bodies[collision.second].body_data.color;
+            self.celestial_bodies[collision.first]
+                .body_data
+                .blend_color(absorbed_color, collision.first_mass, collision.second_mass);
+        }
+        let mut survivors = (0..self.celestial_bodies.len()).collect::<Vec<_>>();
+        retain_survivors(&mut survivors, collisions);
+        retain_survivors(&mut self.celestial_bodies, collisions);
+        // the bodies pinned to an absorbed body are no longer pinned
+        for body in &mut self.celestial_bodies {
+            body.pinned_parent = body
+                .pinned_parent
+                .and_then(|parent| survivors.binary_search(&parent).ok());
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use bevy::render::color::Color;
+
+    use super::*;
+    use crate::planets::planet_bundle::CelestialBodyType;
+    use crate::simulation::{
+        collisions::CollisionResponse, gravity::ForceSolver, physics_config::UnitSystem,
+    };
+
+    /// A scene where every setting differs from its default.
+    pub(crate) fn sample_scene() -> SceneFile {
+        let mut sun = CelestialBodyData::new(
+            String::from("Sun"),
+            CelestialBodyType::Star,
+            1000.,
+            10.,
+            DVec3::new(0., 0., 0.5),
+            Color::YELLOW,
+        );
+        sun.velocity = DVec3::new(0.25, 0., 0.5);
+        sun.emissive_factor = 3.5;
+        sun.light_factor = 1234.;
+        let mut planet = CelestialBodyData::new(
+            String::from("Planet"),
+            CelestialBodyType::Planet,
+            1.,
+            1.,
+            DVec3::new(0., 0., -10.),
+            Color::BLUE,
+        );
+        planet.softening = Some(0.25);
+
+        let mut physics = PhysicsConfig::default();
+        physics.set_unit_system(UnitSystem::Astronomical);
+        physics.force_solver = ForceSolver::BarnesHut;
+        physics.adaptive_stepping.enabled = true;
+
+        SceneFile {
+            version: SCENE_VERSION,
+            celestial_bodies: vec![
+                CelestialBodyRelevantData {
+                    body_data: sun,
+                    position: DVec3::new(1e12 + 0.125, -3., 7.),
+                    pinned_parent: None,
+                },
+                CelestialBodyRelevantData {
+                    body_data: planet,
+                    position: DVec3::new(100., 0., 0.),
+                    pinned_parent: Some(0),
+                },
+            ],
+            camera: CameraPose {
+                position: DVec3::new(1e12, 20., -30.5),
+                rotation: Quat::from_euler(bevy::math::EulerRot::YXZ, 0.3, -0.7, 0.),
+            },
+            physics,
+            settings: SimulationSettings {
+                integrator: IntegratorKind::Rk4,
+                dt: 1. / 128.,
+                time_warp: 10.,
+            },
+            app_config: AppConfig {
+                draw_velocities: false,
+                trajectories_number_iterationss: 42,
+                min_pick_radius: 3.,
+                ..Default::default()
+            },
+            elapsed: 42.5,
+        }
+    }
+
+    #[test]
+    fn scenes_round_trip() {
+        let scene = sample_scene();
+        let json = scene.to_json().unwrap();
+        let reloaded = SceneFile::from_json(&json).unwrap();
+        assert_eq!(reloaded, scene);
+        assert_eq!(reloaded.to_json().unwrap(), json);
+    }
+
+    #[test]
+    fn test_particles_keep_their_mass_when_saved() {
+        let mut moon = CelestialBodyData::new(
+            String::from("Moon"),
+            CelestialBodyType::Moon,
+            7.,
+            1.,
+            DVec3::ZERO,
+            Color::WHITE,
+        );
+        moon.set_body_type(CelestialBodyType::TestParticle);
+        let mut scene = sample_scene();
+        scene.celestial_bodies.push(CelestialBodyRelevantData {
+            body_data: moon,
+            position: DVec3::new(0., 0., 5.),
+            pinned_parent: None,
+        });
+
+        let mut reloaded = SceneFile::from_json(&scene.to_json().unwrap()).unwrap();
+        assert_eq!(reloaded, scene);
+        let moon = &mut reloaded.celestial_bodies[2].body_data;
+        moon.set_body_type(CelestialBodyType::Moon);
+        assert_eq!(moon.mass, 7.);
+    }
+
+    #[test]
+    fn unversioned_scenes_are_migrated() {
+        let json = r#"{
+            "celestial_bodies": [{
+                "body_data": {
+                    "name": "Sun",
+                    "body_type": "Star",
+                    "mass": 1000.0,
+                    "radius": 10.0,
+                    "initial_velocity": [1.0, 2.0, 3.0],
+                    "velocity": [4.0, 5.0, 6.0],
+                    "color": [1.0, 0.5, 0.0],
+                    "emissive_factor": 7.5,
+                    "light_factor": 250.0
+                },
+                "position": [10.0, 20.0, 30.0]
+            }],
+            "camera_position": [0.0, 100.0, 50.0]
+        }"#;
+        let scene = SceneFile::from_json(json).unwrap();
+
+        assert_eq!(scene.version, SCENE_VERSION);
+        assert_eq!(scene.camera.position, DVec3::new(0., 100., 50.));
+        assert_eq!(scene.camera.rotation, CameraPose::default().rotation);
+        assert_eq!(scene.physics, PhysicsConfig::default());
+        assert_eq!(scene.settings, SimulationSettings::default());
+        assert_eq!(scene.app_config, AppConfig::default());
+        assert_eq!(scene.elapsed, 0.);
+
+        let body = &scene.celestial_bodies[0];
+        assert_eq!(body.position, DVec3::new(10., 20., 30.));
+        assert_eq!(body.pinned_parent, None);
+        assert_eq!(body.body_data.initial_velocity, DVec3::new(1., 2., 3.));
+        assert_eq!(body.body_data.velocity, DVec3::new(4., 5., 6.));
+        assert_eq!(body.body_data.emissive_factor, 7.5);
+        assert_eq!(body.body_data.light_factor, 250.);
+        assert_eq!(body.body_data.softening, None);
+    }
+
+    #[test]
+    fn unversioned_physics_settings_are_kept() {
+        let mut physics = PhysicsConfig::default();
+        physics.set_unit_system(UnitSystem::Si);
+        let json = format!(
+            r#"{{ "celestial_bodies": [], "camera_position": [0.0, 0.0, 0.0], "physics": {} }}"#,
+            serde_json::to_string(&physics).unwrap()
+        );
+        assert_eq!(SceneFile::from_json(&json).unwrap().physics, physics);
+    }
+
+    #[test]
+    fn newer_versions_are_rejected() {
+        let mut scene = serde_json::to_value(sample_scene()).unwrap();
+        scene["version"] = Value::from(SCENE_VERSION + 1);
+        assert!(matches!(
+            SceneFile::from_json(&scene.to_string()),
+            Err(SceneError::UnsupportedVersion(version)) if version == SCENE_VERSION + 1
+        ));
+    }
+
+    #[test]
+    fn errors_point_to_their_location() {
+        let error = SceneFile::from_json("{\n  \"version\": 1,\n  oops\n}").unwrap_err();
+        assert_eq!(error.location(), Some((3, 3)));
+        assert_eq!(error.to_string(), "Invalid JSON at line 3, column 3");
+
+        // invalid fields of current scenes are located too
+        let json = sample_scene().to_json().unwrap().replacen(
+            "\"elapsed\": 42.5",
+            "\"elapsed\": \"never\"",
+            1,
+        );
+        let error = SceneFile::from_json(&json).unwrap_err();
+        let line = json
+            .lines()
+            .position(|line| line.contains("never"))
+            .unwrap()
+            + 1;
+        assert_eq!(error.location().map(|(line, _)| line), Some(line));
+    }
+
+    #[test]
+    fn imported_bodies_are_moved_and_renamed() {
+        let options = ImportOptions {
+            offset: DVec3::new(1., 2., 3.),
+            velocity_offset: DVec3::new(0., 0., 1.),
+            name_collision: NameCollision::Rename,
+        };
+        let existing = HashSet::from([String::from("Sun"), String::from("Sun (2)")]);
+        let scene = sample_scene();
+        let bodies = scene.clone().imported_bodies(&options, &existing);
+
+        let names = bodies
+            .iter()
+            .map(|body| body.body_data.name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["Sun (3)", "Planet"]);
+        for (body, original) in bodies.iter().zip(&scene.celestial_bodies) {
+            assert_eq!(body.position, original.position + options.offset);
+            assert_eq!(
+                body.body_data.velocity,
+                original.body_data.velocity + options.velocity_offset
+            );
+            assert_eq!(body.pinned_parent, original.pinned_parent);
+        }
+    }
+
+    #[test]
+    fn skipped_bodies_are_left_out() {
+        let options = ImportOptions {
+            name_collision: NameCollision::Skip,
+            ..Default::default()
+        };
+        let existing = HashSet::from([String::from("Sun")]);
+        let bodies = sample_scene().imported_bodies(&options, &existing);
+
+        assert_eq!(bodies.len(), 1);
+        assert_eq!(bodies[0].body_data.name, "Planet");
+        // its parent was skipped
+        assert_eq!(bodies[0].pinned_parent, None);
+    }
+
+    #[test]
+    fn absorbed_parents_are_unpinned() {
+        let mut scene = sample_scene();
+        let mut moon = scene.celestial_bodies[1].clone();
+        moon.pinned_parent = Some(2);
+        let mut absorbed_moon = moon.clone();
+        absorbed_moon.pinned_parent = Some(1);
+        scene
+            .celestial_bodies
+            .push(scene.celestial_bodies[1].clone());
+        scene.celestial_bodies.push(moon);
+        scene.celestial_bodies.push(absorbed_moon);
+
+        // the first planet is absorbed by the sun
+        let collision = Collision {
+            first: 0,
+            second: 1,
+            first_mass: 1000.,
+            second_mass: 1.,
+            relative_speed: 1.,
+            response: CollisionResponse::Merge,
+        };
+        scene.apply_collisions(&[collision]);
+
+        let parents = scene
+            .celestial_bodies
+            .iter()
+            .map(|body| body.pinned_parent)
+            .collect::<Vec<_>>();
+        assert_eq!(parents, [None, Some(0), Some(1), None]);
+    }
+
+    #[test]
+    fn read_errors_name_the_file() {
+        let path = std::env::temp_dir().join("solarust_missing_scene.json");
+        let error = read_scene(&path).unwrap_err();
+        assert!(matches!(error, SceneIoError::Read(..)));
+        assert_eq!(error.path(), path);
+        assert_eq!(error.details()[0], path.display().to_string());
+    }
+}
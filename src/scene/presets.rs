@@ -0,0 +1,607 @@
+use std::f64::consts::PI;
+use std::fmt;
+use std::str::FromStr;
+
+use bevy::math::{DQuat, DVec3};
+use bevy::render::color::Color;
+
+use super::{CameraPose, CelestialBodyRelevantData, SceneFile, SimulationSettings};
+use crate::planets::planet_bundle::{CelestialBodyData, CelestialBodyType};
+use crate::simulation::{
+    collisions::CollisionResponse, integrators::IntegratorKind, physics_config::PhysicsConfig,
+};
+
+/// The mass of the Sun in the presets, which use the toy units (`G = 1`).
+const SUN_MASS: f64 = 2000.;
+
+/// The length of an astronomical unit in the presets, small enough for the
+/// outer planets to remain in front of the camera's far plane.
+const AU: f64 = 20.;
+
+/// How many asteroids gather around each of the Sun-Jupiter Lagrange points.
+const TROJANS_PER_POINT: usize = 15;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// The scenes shipped with the application.
+pub enum Preset {
+    /// The Sun and the eight planets, with their real relative masses and orbits.
+    SolarSystem,
+    /// Three equal masses chasing each other along a figure-eight.
+    FigureEight,
+    /// Two stars orbiting each other, and a planet orbiting both.
+    BinaryStar,
+    /// Asteroids sharing Jupiter's orbit around its L4 and L5 points.
+    Trojans,
+    /// Burrau's problem: three masses released at rest, until one is ejected.
+    Pythagorean,
+}
+
+impl Preset {
+    /// Every preset, in the order they are shown in the UI.
+    pub const ALL: [Preset; 5] = [
+        Preset::SolarSystem,
+        Preset::FigureEight,
+        Preset::BinaryStar,
+        Preset::Trojans,
+        Preset::Pythagorean,
+    ];
+
+    /// The short name of the preset, as used on the command line.
+    pub fn id(self) -> &'static str {
+        match self {
+            Preset::SolarSystem => "solar-system",
+            Preset::FigureEight => "figure-eight",
+            Preset::BinaryStar => "binary-star",
+            Preset::Trojans => "trojans",
+            Preset::Pythagorean => "pythagorean",
+        }
+    }
+
+    /// Builds the scene of the preset.
+    pub fn scene(self) -> SceneFile {
+        match self {
+            Preset::SolarSystem => solar_system(),
+            Preset::FigureEight => figure_eight(),
+            Preset::BinaryStar => binary_star(),
+            Preset::Trojans => trojans(),
+            Preset::Pythagorean => pythagorean(),
+        }
+    }
+}
+
+impl fmt::Display for Preset {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Preset::SolarSystem => "Solar System",
+            Preset::FigureEight => "Figure-eight three-body",
+            Preset::BinaryStar => "Binary star",
+            Preset::Trojans => "Sun-Jupiter Trojans",
+            Preset::Pythagorean => "Pythagorean three-body",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl FromStr for Preset {
+    type Err = String;
+
+    /// Parses the short name of a preset, as used on the command line.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.to_lowercase();
+        Preset::ALL
+            .into_iter()
+            .find(|preset| preset.id() == s)
+            .ok_or_else(|| {
+                let ids = Preset::ALL.map(Preset::id);
+                format!("Unknown preset {} (expected one of {})", s, ids.join(", "))
+            })
+    }
+}
+
+/// A point of the orbital plane, which is horizontal.
+fn in_plane(x: f64, y: f64) -> DVec3 {
+    DVec3::new(x, 0., y)
+}
+
+/// The velocity of a point of the orbital plane rotating at `angular_velocity`
+/// around the origin.
+fn rotating(position: DVec3, angular_velocity: f64) -> DVec3 {
+    DVec3::Y.cross(position) * -angular_velocity
+}
+
+/// The position and velocity of a body at the perihelion of an orbit around a
+/// body of mass `central_mass` resting at the origin.
+///
+/// The perihelion is at `longitude` in the orbital plane, the orbit being
+/// tilted by `inclination` around the line joining it to the origin.
+fn orbit(
+    central_mass: f64,
+    semi_major_axis: f64,
+    eccentricity: f64,
+    inclination: f64,
+    longitude: f64,
+) -> (DVec3, DVec3) {
+    let distance = semi_major_axis * (1. - eccentricity);
+    // vis-viva equation
+    let speed = (central_mass * (2. / distance - 1. / semi_major_axis)).sqrt();
+    let direction = in_plane(longitude.cos(), longitude.sin());
+    let tilt = DQuat::from_axis_angle(direction, inclination);
+    (direction * distance, tilt * rotating(direction, 1.) * speed)
+}
+
+/// A body of a preset, moving at `velocity`.
+fn body(
+    name: &str,
+    body_type: CelestialBodyType,
+    mass: f64,
+    radius: f64,
+    color: Color,
+    position: DVec3,
+    velocity: DVec3,
+) -> CelestialBodyRelevantData {
+    CelestialBodyRelevantData {
+        body_data: CelestialBodyData::new(
+            String::from(name),
+            body_type,
+            mass,
+            radius,
+            velocity,
+            color,
+        ),
+        position,
+        pinned_parent: None,
+    }
+}
+
+/// Moves the bodies to the frame of their center of mass, so that the system
+/// does not drift away.
+fn centered(mut bodies: Vec<CelestialBodyRelevantData>) -> Vec<CelestialBodyRelevantData> {
+    let total_mass = bodies.iter().map(|body| body.body_data.mass).sum::<f64>();
+    let (center, velocity) = bodies.iter().fold((DVec3::ZERO, DVec3::ZERO), |sum, body| {
+        let mass = body.body_data.mass / total_mass;
+        (
+            sum.0 + body.position * mass,
+            sum.1 + body.body_data.velocity * mass,
+        )
+    });
+    for body in &mut bodies {
+        body.position -= center;
+        body.body_data.velocity -= velocity;
+        body.body_data.initial_velocity = body.body_data.velocity;
+    }
+    bodies
+}
+
+/// A scene of point masses, seen from `height` above the orbital plane.
+fn preset_scene(
+    bodies: Vec<CelestialBodyRelevantData>,
+    height: f64,
+    settings: SimulationSettings,
+) -> SceneFile {
+    SceneFile {
+        celestial_bodies: bodies,
+        camera: CameraPose::above(height),
+        physics: PhysicsConfig {
+            softening: 0.,
+            ..Default::default()
+        },
+        settings,
+        ..Default::default()
+    }
+}
+
+/// The Sun and the planets, starting at their perihelion.
+fn solar_system() -> SceneFile {
+    // mass (solar masses), semi-major axis (AU), eccentricity, inclination and
+    // longitude of the perihelion (degrees), displayed radius and color
+    let planets = [
+        (
+            "Mercury",
+            1.660e-7,
+            0.387,
+            0.2056,
+            7.00,
+            77.46,
+            0.3,
+            [0.6, 0.6, 0.6],
+        ),
+        (
+            "Venus",
+            2.448e-6,
+            0.723,
+            0.0068,
+            3.39,
+            131.6,
+            0.5,
+            [0.9, 0.8, 0.5],
+        ),
+        (
+            "Earth",
+            3.003e-6,
+            1.000,
+            0.0167,
+            0.00,
+            102.9,
+            0.5,
+            [0.2, 0.4, 1.0],
+        ),
+        (
+            "Mars",
+            3.227e-7,
+            1.524,
+            0.0934,
+            1.85,
+            336.0,
+            0.4,
+            [0.8, 0.3, 0.1],
+        ),
+        (
+            "Jupiter",
+            9.546e-4,
+            5.203,
+            0.0489,
+            1.30,
+            14.73,
+            1.8,
+            [0.8, 0.6, 0.4],
+        ),
+        (
+            "Saturn",
+            2.858e-4,
+            9.537,
+            0.0565,
+            2.49,
+            92.43,
+            1.5,
+            [0.9, 0.8, 0.6],
+        ),
+        (
+            "Uranus",
+            4.366e-5,
+            19.19,
+            0.0463,
+            0.77,
+            170.9,
+            1.0,
+            [0.5, 0.8, 0.9],
+        ),
+        (
+            "Neptune",
+            5.151e-5,
+            30.07,
+            0.0097,
+            1.77,
+            44.97,
+            1.0,
+            [0.2, 0.3, 0.9],
+        ),
+    ];
+    let mut bodies = vec![body(
+        "Sun",
+        CelestialBodyType::Star,
+        SUN_MASS,
+        3.,
+        Color::rgb(1., 0.9, 0.6),
+        DVec3::ZERO,
+        DVec3::ZERO,
+    )];
+    for (name, mass, semi_major_axis, eccentricity, inclination, longitude, radius, [r, g, b]) in
+        planets
+    {
+        let (position, velocity) = orbit(
+            SUN_MASS,
+            semi_major_axis * AU,
+            eccentricity,
+            f64::to_radians(inclination),
+            f64::to_radians(longitude),
+        );
+        bodies.push(body(
+            name,
+            CelestialBodyType::Planet,
+            mass * SUN_MASS,
+            radius,
+            Color::rgb(r, g, b),
+            position,
+            velocity,
+        ));
+    }
+
+    preset_scene(
+        centered(bodies),
+        300.,
+        SimulationSettings {
+            time_warp: 4.,
+            ..Default::default()
+        },
+    )
+}
+
+/// The periodic orbit found by Chenciner and Montgomery, from the initial
+/// conditions computed by Simó.
+fn figure_eight() -> SceneFile {
+    // the orbit is given for unit masses and `G = 1`, and is scaled up here:
+    // velocities scale as the square root of mass over length
+    let (length, mass): (f64, f64) = (100., 10_000.);
+    let speed = (mass / length).sqrt();
+    let position = in_plane(0.970_004_36, -0.243_087_53) * length;
+    let velocity = in_plane(-0.932_407_37, -0.864_731_46) * speed;
+    let bodies = [
+        ("Alpha", position, -velocity / 2., Color::rgb(1., 0.4, 0.3)),
+        ("Beta", -position, -velocity / 2., Color::rgb(0.4, 1., 0.5)),
+        ("Gamma", DVec3::ZERO, velocity, Color::rgb(0.4, 0.6, 1.)),
+    ]
+    .map(|(name, position, velocity, color)| {
+        body(
+            name,
+            CelestialBodyType::Star,
+            mass,
+            4.,
+            color,
+            position,
+            velocity,
+        )
+    });
+
+    preset_scene(
+        bodies.into(),
+        300.,
+        SimulationSettings {
+            time_warp: 4.,
+            ..Default::default()
+        },
+    )
+}
+
+/// Two stars on a circular orbit, and a planet on a stable circumbinary orbit
+/// four times as wide.
+fn binary_star() -> SceneFile {
+    let (primary_mass, secondary_mass) = (0.6 * SUN_MASS, 0.4 * SUN_MASS);
+    let total_mass = primary_mass + secondary_mass;
+    let separation: f64 = 30.;
+    let angular_velocity = (total_mass / separation.powi(3)).sqrt();
+    let primary = in_plane(-separation * secondary_mass / total_mass, 0.);
+    let secondary = in_plane(separation * primary_mass / total_mass, 0.);
+    let (planet, planet_velocity) = orbit(total_mass, 4. * separation, 0., 0., PI / 2.);
+
+    let bodies = vec![
+        body(
+            "Primary",
+            CelestialBodyType::Star,
+            primary_mass,
+            4.,
+            Color::rgb(1., 0.9, 0.6),
+            primary,
+            rotating(primary, angular_velocity),
+        ),
+        body(
+            "Secondary",
+            CelestialBodyType::Star,
+            secondary_mass,
+            3.,
+            Color::rgb(1., 0.5, 0.3),
+            secondary,
+            rotating(secondary, angular_velocity),
+        ),
+        body(
+            "Circumbinary planet",
+            CelestialBodyType::Planet,
+            1.,
+            1.5,
+            Color::rgb(0.3, 0.7, 0.6),
+            planet,
+            planet_velocity,
+        ),
+    ];
+
+    preset_scene(
+        centered(bodies),
+        350.,
+        SimulationSettings {
+            time_warp: 8.,
+            ..Default::default()
+        },
+    )
+}
+
+/// The Sun and Jupiter on a circular orbit, with massless asteroids scattered
+/// around the L4 and L5 points, which lead and trail Jupiter by 60°.
+fn trojans() -> SceneFile {
+    let jupiter_mass = 9.546e-4 * SUN_MASS;
+    let total_mass = SUN_MASS + jupiter_mass;
+    let distance = 5.203 * AU;
+    let angular_velocity = (total_mass / distance.powi(3)).sqrt();
+    let sun = in_plane(-distance * jupiter_mass / total_mass, 0.);
+    let jupiter = in_plane(distance * SUN_MASS / total_mass, 0.);
+
+    let mut bodies = vec![
+        body(
+            "Sun",
+            CelestialBodyType::Star,
+            SUN_MASS,
+            3.,
+            Color::rgb(1., 0.9, 0.6),
+            sun,
+            rotating(sun, angular_velocity),
+        ),
+        body(
+            "Jupiter",
+            CelestialBodyType::Planet,
+            jupiter_mass,
+            1.8,
+            Color::rgb(0.8, 0.6, 0.4),
+            jupiter,
+            rotating(jupiter, angular_velocity),
+        ),
+    ];
+    for (point, angle) in [("L4", PI / 3.), ("L5", -PI / 3.)] {
+        // the points form equilateral triangles with the Sun and Jupiter
+        let lagrange_point = sun + DQuat::from_rotation_y(-angle) * (jupiter - sun);
+        for index in 0..TROJANS_PER_POINT {
+            // spread the asteroids evenly but not in lines, up to 8° and 0.5% away
+            let spread = |step: usize| {
+                ((index * step) % TROJANS_PER_POINT) as f64 / (TROJANS_PER_POINT - 1) as f64 - 0.5
+            };
+            let position = DQuat::from_rotation_y(spread(7) * 16_f64.to_radians())
+                * lagrange_point
+                * (1. + spread(11) * 0.01);
+            bodies.push(body(
+                &format!("Trojan {} {}", point, index + 1),
+                CelestialBodyType::TestParticle,
+                0.,
+                0.3,
+                Color::rgb(0.6, 0.6, 0.5),
+                position,
+                rotating(position, angular_velocity),
+            ));
+        }
+    }
+
+    preset_scene(
+        bodies,
+        300.,
+        SimulationSettings {
+            time_warp: 10.,
+            ..Default::default()
+        },
+    )
+}
+
+/// Masses of 3, 4 and 5 released at rest at the vertices of a right triangle
+/// whose sides are 3, 4 and 5 long, each mass facing the side of its length.
+fn pythagorean() -> SceneFile {
+    // scaled like the figure-eight, the close encounters are resolved by the
+    // adaptive time stepping and slightly softened, and the bodies pass
+    // through each other
+    let (length, mass) = (30., 1000.);
+    let bodies = [
+        ("Three", 3., in_plane(1., 3.), Color::rgb(1., 0.4, 0.3)),
+        ("Four", 4., in_plane(-2., -1.), Color::rgb(0.4, 1., 0.5)),
+        ("Five", 5., in_plane(1., -1.), Color::rgb(0.4, 0.6, 1.)),
+    ]
+    .map(|(name, masses, position, color)| {
+        body(
+            name,
+            CelestialBodyType::Star,
+            masses * mass,
+            1. + masses / 2.,
+            color,
+            position * length,
+            DVec3::ZERO,
+        )
+    });
+
+    let mut scene = preset_scene(
+        centered(bodies.into()),
+        400.,
+        SimulationSettings {
+            integrator: IntegratorKind::Rk4,
+            time_warp: 5.,
+            ..Default::default()
+        },
+    );
+    scene.physics.collision_response = CollisionResponse::Ignore;
+    scene.physics.softening = 0.3;
+    scene.physics.adaptive_stepping.enabled = true;
+    scene.physics.adaptive_stepping.min_dt = 1e-6;
+    scene.physics.adaptive_stepping.tolerance = 1e-5;
+    scene
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f64::consts::TAU;
+
+    use super::*;
+    use crate::simulation::Body;
+
+    #[test]
+    fn presets_are_parsed_from_their_id() {
+        for preset in Preset::ALL {
+            assert_eq!(preset.id().parse(), Ok(preset));
+        }
+        assert!("moon".parse::<Preset>().is_err());
+    }
+
+    #[test]
+    fn presets_do_not_drift() {
+        for preset in Preset::ALL {
+            let scene = preset.scene();
+            let simulation = scene.to_simulation(scene.settings.integrator);
+            let diagnostics = simulation.diagnostics();
+            let scale = scene
+                .celestial_bodies
+                .iter()
+                .map(|body| body.body_data.mass * body.body_data.velocity.length())
+                .sum::<f64>();
+            assert!(
+                diagnostics.momentum.length() <= scale * 1e-9,
+                "{} drifts",
+                preset
+            );
+        }
+    }
+
+    #[test]
+    fn figure_eight_is_periodic() {
+        let scene = Preset::FigureEight.scene();
+        let mut simulation = scene.to_simulation(IntegratorKind::Rk4);
+        // the period for unit masses and lengths is 6.3259, scaled by the
+        // square root of length cubed over mass
+        let period = 6.325_9 * (100_f64.powi(3) / 10_000.).sqrt();
+        let dt = 1. / 64.;
+        for _ in 0..(period / dt).round() as usize {
+            simulation.advance(dt);
+        }
+        for (body, initial) in simulation.bodies.iter().zip(&scene.celestial_bodies) {
+            assert!(
+                body.position.distance(initial.position) < 1.,
+                "{} ended at {} instead of {}",
+                initial.body_data.name,
+                body.position,
+                initial.position
+            );
+        }
+    }
+
+    #[test]
+    fn trojans_stay_around_lagrange_points() {
+        let scene = Preset::Trojans.scene();
+        let mut simulation = scene.to_simulation(scene.settings.integrator);
+        let jupiter = &scene.celestial_bodies[1];
+        let period = TAU * jupiter.position.length() / jupiter.body_data.velocity.length();
+        // the signed angle from Jupiter to `position` around the Sun, in the XZ plane
+        let angle_from_jupiter = |bodies: &[Body], position: DVec3| {
+            let jupiter = bodies[1].position - bodies[0].position;
+            let trojan = position - bodies[0].position;
+            jupiter
+                .cross(trojan)
+                .y
+                .atan2(jupiter.x * trojan.x + jupiter.z * trojan.z)
+                .to_degrees()
+        };
+        let sides = simulation.bodies[2..]
+            .iter()
+            .map(|trojan| angle_from_jupiter(&simulation.bodies, trojan.position).signum())
+            .collect::<Vec<_>>();
+
+        let steps = 1000;
+        for _ in 0..5 {
+            for _ in 0..steps {
+                simulation.advance(period / steps as f64);
+            }
+            assert_eq!(simulation.bodies.len(), scene.celestial_bodies.len());
+            let trojans = simulation.bodies[2..]
+                .iter()
+                .zip(&scene.celestial_bodies[2..]);
+            for ((trojan, initial), side) in trojans.zip(&sides) {
+                let angle = angle_from_jupiter(&simulation.bodies, trojan.position) * side;
+                assert!(
+                    (angle - 60.).abs() < 30.,
+                    "{} is {:.1}° away from Jupiter",
+                    initial.body_data.name,
+                    angle
+                );
+            }
+        }
+    }
+}
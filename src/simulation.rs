@@ -0,0 +1,188 @@
+use bevy::{log::warn, math::DVec3};
+
+use collisions::{resolve_collisions, Collision};
+use gravity::Gravity;
+use integrators::IntegratorKind;
+use physics_config::PhysicsConfig;
+
+pub mod adaptive;
+pub mod barnes_hut;
+pub mod collisions;
+pub mod diagnostics;
+pub mod gravity;
+pub mod integrators;
+pub mod orbits;
+pub mod physics_config;
+
+#[derive(Debug, Clone, PartialEq)]
+/// The physical state of a body, as seen by the simulation.
+pub struct Body {
+    /// The body's mass.
+    pub mass: f64,
+    /// The body's radius.
+    pub radius: f64,
+    /// The body's position.
+    pub position: DVec3,
+    /// The body's velocity.
+    pub velocity: DVec3,
+    /// Overrides the global softening length for this body.
+    pub softening: Option<f64>,
+    /// The gravitational acceleration evaluated during the last step.
+    pub acceleration: DVec3,
+}
+
+impl Body {
+    /// Returns true if the body's state is made of finite numbers.
+    fn is_finite(&self) -> bool {
+        self.position.is_finite() && self.velocity.is_finite() && self.acceleration.is_finite()
+    }
+}
+
+#[derive(Debug, Clone)]
+/// A deterministic N-body simulation, independent from Bevy's ECS and rendering.
+///
+/// The `PlanetPlugin` systems build one from the ECS each tick, while the
+/// headless mode drives it directly.
+pub struct Simulation {
+    /// The simulated bodies.
+    pub bodies: Vec<Body>,
+    /// The physical constants and collision handling.
+    pub physics: PhysicsConfig,
+    /// The integrator used to advance the bodies.
+    pub integrator: IntegratorKind,
+    /// The simulated time elapsed since the simulation was created.
+    pub elapsed: f64,
+    /// The sub-step the adaptive stepping will try first, carried between calls
+    /// to `advance` by whoever drives the simulation.
+    pub substep: Option<f64>,
+}
+
+impl Simulation {
+    /// Constructs an empty `Simulation`.
+    pub fn new(physics: PhysicsConfig, integrator: IntegratorKind) -> Self {
+        Self {
+            bodies: Vec::new(),
+            physics,
+            integrator,
+            elapsed: 0.,
+            substep: None,
+        }
+    }
+
+    /// Advances every body by `dt`, then resolves the collisions between them.
+    ///
+    /// Bodies absorbed by a merge are removed, see `collisions::retain_survivors`
+    /// to keep collections indexed like the bodies in sync.
+    pub fn step(&mut self, dt: f64) -> Vec<Collision> {
+        self.integrate(dt);
+        self.elapsed += dt;
+        resolve_collisions(&mut self.bodies, self.physics.collision_response)
+    }
+
+    /// The masses and the resolved softening lengths of the bodies, from which
+    /// their `Gravity` is built.
+    fn masses_and_softenings(&self) -> (Vec<f64>, Vec<f64>) {
+        self.bodies
+            .iter()
+            .map(|b| (b.mass, b.softening.unwrap_or(self.physics.softening)))
+            .unzip()
+    }
+
+    /// The gravitational interactions between bodies of the given masses and
+    /// softening lengths, with the simulation's settings.
+    fn gravity<'a>(&self, masses: &'a [f64], softenings: &'a [f64]) -> Gravity<'a> {
+        Gravity {
+            g: self.physics.g,
+            masses,
+            softenings,
+            solver: self.physics.force_solver,
+            opening_angle: self.physics.opening_angle,
+            parallel: self.physics.parallel,
+        }
+    }
+
+    /// Advances every body by `dt` with the integrator, ignoring collisions.
+    fn integrate(&mut self, dt: f64) {
+        let mut positions = self.bodies.iter().map(|b| b.position).collect::<Vec<_>>();
+        let mut velocities = self.bodies.iter().map(|b| b.velocity).collect::<Vec<_>>();
+        let (masses, softenings) = self.masses_and_softenings();
+        let gravity = self.gravity(&masses, &softenings);
+
+        let accelerations =
+            self.integrator
+                .integrator()
+                .step(&mut positions, &mut velocities, &gravity, dt);
+
+        for (i, (body, ((position, velocity), acceleration))) in self
+            .bodies
+            .iter_mut()
+            .zip(positions.into_iter().zip(velocities).zip(accelerations))
+            .enumerate()
+        {
+            let previous_position = body.position;
+            body.position = position;
+            body.velocity = velocity;
+            body.acceleration = acceleration;
+            // a single bad encounter must not poison the whole scene: keep the
+            // body where it was and stop it rather than propagating NaNs.
+            if !body.is_finite() {
+                warn!("Body {} reached a non finite state, resetting it", i);
+                body.position = previous_position;
+                body.velocity = DVec3::ZERO;
+                body.acceleration = DVec3::ZERO;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Two bodies so close to each other that, without softening, the force
+    /// between them overflows.
+    fn close_encounter(softening: f64) -> Simulation {
+        let physics = PhysicsConfig {
+            softening,
+            parallel: false,
+            ..Default::default()
+        };
+        let mut simulation = Simulation::new(physics, IntegratorKind::VelocityVerlet);
+        simulation.bodies = [-1e-160, 1e-160]
+            .map(|x| Body {
+                mass: 1.,
+                radius: 0.,
+                position: DVec3::new(x, 0., 0.),
+                velocity: DVec3::ZERO,
+                softening: None,
+                acceleration: DVec3::ZERO,
+            })
+            .to_vec();
+        simulation
+    }
+
+    #[test]
+    fn softening_keeps_close_encounters_finite() {
+        let mut simulation = close_encounter(0.1);
+        let initial = simulation.bodies.clone();
+        simulation.integrate(0.01);
+        for (body, initial) in simulation.bodies.iter().zip(&initial) {
+            assert!(body.is_finite());
+            // the guard did not stop the bodies: they still feel each other
+            assert_ne!(body.acceleration, DVec3::ZERO);
+            assert_ne!(body.velocity, initial.velocity);
+        }
+    }
+
+    #[test]
+    fn non_finite_bodies_are_reset() {
+        let mut simulation = close_encounter(0.);
+        let initial = simulation.bodies.clone();
+        simulation.integrate(0.01);
+        for (body, initial) in simulation.bodies.iter().zip(&initial) {
+            assert_eq!(body.position, initial.position);
+            assert_eq!(body.velocity, DVec3::ZERO);
+            assert_eq!(body.acceleration, DVec3::ZERO);
+        }
+    }
+}
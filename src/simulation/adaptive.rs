@@ -0,0 +1,309 @@
+use bevy::log::warn;
+use serde::{Deserialize, Serialize};
+
+use super::collisions::{resolve_collisions, retain_survivors, Collision};
+use super::Simulation;
+
+/// The most sub-steps in a single call to `advance`, the last one simulating
+/// the rest of the time in one go so a tiny minimum step cannot freeze the app.
+const MAX_SUBSTEPS: usize = 10_000;
+
+/// How many body sub-steps (sub-steps times bodies) a call to `advance_many`
+/// may take; the steps past it are dropped, slowing down the time warp.
+const BODY_SUBSTEP_BUDGET: usize = 1_000_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+/// Settings of the adaptive time stepping.
+pub struct AdaptiveStepping {
+    /// Whether each step is split in sub-steps sized to keep the error bounded.
+    pub enabled: bool,
+    /// The smallest sub-step allowed.
+    pub min_dt: f64,
+    /// The largest sub-step allowed.
+    pub max_dt: f64,
+    /// The tolerated position error of a sub-step, relative to how far the body
+    /// moved during it (or to its radius or softening length for slow bodies).
+    pub tolerance: f64,
+}
+
+impl Default for AdaptiveStepping {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            min_dt: 1e-5,
+            max_dt: 1. / 64.,
+            tolerance: 1e-3,
+        }
+    }
+}
+
+/// The outcome of `Simulation::advance`.
+pub struct StepReport {
+    /// The collisions that happened, indices referring to the bodies as they
+    /// were before the call.
+    pub collisions: Vec<Collision>,
+    /// How many sub-steps were taken.
+    pub substeps: usize,
+    /// How many of the requested steps were taken.
+    pub steps: usize,
+}
+
+impl Simulation {
+    /// Advances the simulation by `dt`, splitting it in sub-steps sized by the
+    /// adaptive stepping when it is enabled.
+    pub fn advance(&mut self, dt: f64) -> StepReport {
+        self.advance_within(dt, MAX_SUBSTEPS)
+    }
+
+    /// Same as `advance`, simulating the remaining time at once in the last
+    /// of at most `max_substeps` sub-steps.
+    fn advance_within(&mut self, dt: f64, max_substeps: usize) -> StepReport {
+        let mut settings = self.physics.adaptive_stepping;
+        if !settings.enabled {
+            return StepReport {
+                collisions: self.step(dt),
+                substeps: 1,
+                steps: 1,
+            };
+        }
+
+        // keep the limits usable whatever was typed in the UI
+        settings.min_dt = settings.min_dt.max(dt * 1e-6);
+        settings.max_dt = settings.max_dt.max(settings.min_dt);
+
+        let mut original_indices = (0..self.bodies.len()).collect::<Vec<_>>();
+        let mut collisions = Vec::new();
+        let mut substeps = 0;
+        let mut remaining = dt;
+        let mut substep = self
+            .substep
+            .unwrap_or(settings.max_dt)
+            .clamp(settings.min_dt, settings.max_dt);
+
+        while remaining > dt * 1e-6 {
+            let exhausted = substeps + 1 >= max_substeps;
+            let h = if exhausted {
+                warn!(
+                    "Too many sub-steps, simulating the remaining {}s at once",
+                    remaining
+                );
+                remaining
+            } else {
+                substep.min(remaining)
+            };
+            let (next_substep, accepted) = self.try_substep(h, &settings, exhausted);
+            if !accepted {
+                substep = next_substep;
+                continue;
+            }
+            // a sub-step shortened to end exactly at `dt` says nothing about the next one
+            substep = if h < substep {
+                substep.max(next_substep)
+            } else {
+                next_substep
+            };
+            remaining -= h;
+            substeps += 1;
+
+            let substep_collisions =
+                resolve_collisions(&mut self.bodies, self.physics.collision_response);
+            remap_collisions(&substep_collisions, &mut original_indices, &mut collisions);
+        }
+
+        self.substep = Some(substep);
+        StepReport {
+            collisions,
+            substeps,
+            steps: 1,
+        }
+    }
+
+    /// Advances the simulation `steps` times by `dt`, as done when time is warped.
+    ///
+    /// The steps stop early once the sub-step budget, shrinking as bodies are
+    /// added, is spent; `StepReport::steps` tells how many were taken.
+    pub fn advance_many(&mut self, dt: f64, steps: usize) -> StepReport {
+        let budget = (BODY_SUBSTEP_BUDGET / self.bodies.len().max(1)).clamp(1, MAX_SUBSTEPS);
+        let mut original_indices = (0..self.bodies.len()).collect::<Vec<_>>();
+        let mut report = StepReport {
+            collisions: Vec::new(),
+            substeps: 0,
+            steps: 0,
+        };
+        for _ in 0..steps {
+            if report.substeps >= budget {
+                break;
+            }
+            let step = self.advance_within(dt, budget - report.substeps);
+            report.substeps += step.substeps;
+            report.steps += 1;
+            remap_collisions(
+                &step.collisions,
+                &mut original_indices,
+                &mut report.collisions,
+            );
+        }
+        report
+    }
+
+    /// Tries to advance by `h`, estimating the error by comparing a full step
+    /// with two half steps (step doubling).
+    ///
+    /// Returns the sub-step to try next and whether this one was accepted,
+    /// which it always is when `force` is set.
+    fn try_substep(&mut self, h: f64, settings: &AdaptiveStepping, force: bool) -> (f64, bool) {
+        let mut full = self.clone();
+        full.integrate(h);
+        let mut half = self.clone();
+        half.integrate(h / 2.);
+        half.integrate(h / 2.);
+
+        let error = self
+            .bodies
+            .iter()
+            .zip(full.bodies.iter().zip(&half.bodies))
+            .map(|(start, (full, half))| {
+                // the softening length keeps the particles, which have no
+                // radius, from being held to a zero error when barely moving
+                let softening = start.softening.unwrap_or(self.physics.softening);
+                let scale = settings.tolerance
+                    * start
+                        .position
+                        .distance(half.position)
+                        .max(start.radius)
+                        .max(softening);
+                full.position.distance(half.position) / scale.max(f64::MIN_POSITIVE)
+            })
+            .fold(0., f64::max);
+
+        // the local error of a scheme of order p scales as h^(p + 1)
+        let order = self.integrator.order();
+        let factor = if error > 0. {
+            (0.9 * error.powf(-1. / (order + 1) as f64)).clamp(0.2, 2.)
+        } else {
+            2.
+        };
+        let next_substep = (h * factor).clamp(settings.min_dt, settings.max_dt);
+
+        if error <= 1. || h <= settings.min_dt || force {
+            self.bodies = half.bodies;
+            self.elapsed += h;
+            (next_substep, true)
+        } else {
+            (next_substep, false)
+        }
+    }
+}
+
+/// Pushes `collisions` to `into` with their indices mapped back to the bodies
+/// as they were before the first step, then forgets the absorbed bodies.
+fn remap_collisions(
+    collisions: &[Collision],
+    original_indices: &mut Vec<usize>,
+    into: &mut Vec<Collision>,
+) {
+    into.extend(collisions.iter().cloned().map(|mut collision| {
+        collision.first = original_indices[collision.first];
+        collision.second = original_indices[collision.second];
+        collision
+    }));
+    retain_survivors(original_indices, collisions);
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::math::DVec3;
+
+    use super::*;
+    use crate::simulation::{integrators::IntegratorKind, physics_config::PhysicsConfig, Body};
+
+    fn body(mass: f64, radius: f64, position: DVec3, velocity: DVec3) -> Body {
+        Body {
+            mass,
+            radius,
+            position,
+            velocity,
+            softening: None,
+            acceleration: DVec3::ZERO,
+        }
+    }
+
+    /// A light body on an eccentric orbit around a heavy one, the bodies
+    /// passing close to each other.
+    fn eccentric_orbit(adaptive: bool) -> Simulation {
+        let mut physics = PhysicsConfig {
+            softening: 0.,
+            parallel: false,
+            ..Default::default()
+        };
+        physics.adaptive_stepping.enabled = adaptive;
+        physics.adaptive_stepping.max_dt = 0.1;
+        physics.adaptive_stepping.tolerance = 1e-6;
+        let mut simulation = Simulation::new(physics, IntegratorKind::VelocityVerlet);
+        simulation.bodies = vec![
+            body(1., 0.01, DVec3::ZERO, DVec3::ZERO),
+            body(1e-6, 0.01, DVec3::new(1., 0., 0.), DVec3::new(0., 0., 0.4)),
+        ];
+        simulation
+    }
+
+    #[test]
+    fn sub_steps_converge_to_the_fine_solution() {
+        let duration = 3.;
+        let mut fine = eccentric_orbit(false);
+        let steps = 300_000;
+        for _ in 0..steps {
+            fine.step(duration / steps as f64);
+        }
+        let mut coarse = eccentric_orbit(false);
+        coarse.advance(duration);
+        let mut adaptive = eccentric_orbit(true);
+        let report = adaptive.advance(duration);
+
+        let error = |simulation: &Simulation| {
+            simulation.bodies[1]
+                .position
+                .distance(fine.bodies[1].position)
+        };
+        assert!(report.substeps > 1);
+        assert!(error(&adaptive) < 1e-3, "off by {}", error(&adaptive));
+        assert!(error(&adaptive) < error(&coarse) / 100.);
+    }
+
+    #[test]
+    fn the_sub_step_budget_is_respected() {
+        let mut simulation = eccentric_orbit(true);
+        simulation.physics.adaptive_stepping.min_dt = 1e-9;
+        simulation.physics.adaptive_stepping.tolerance = 1e-12;
+        let report = simulation.advance_many(0.1, 1000);
+
+        assert!(
+            report.substeps <= MAX_SUBSTEPS,
+            "{} sub-steps",
+            report.substeps
+        );
+        assert!(report.steps < 1000);
+    }
+
+    #[test]
+    fn particles_at_rest_do_not_shorten_the_sub_steps() {
+        let mut binary = eccentric_orbit(true);
+        binary.physics.softening = 0.1;
+        binary.physics.adaptive_stepping.tolerance = 1e-3;
+        binary.bodies = vec![
+            body(1., 0.01, DVec3::new(-1., 0., 0.), DVec3::new(0., 0., -0.5)),
+            body(1., 0.01, DVec3::new(1., 0., 0.), DVec3::new(0., 0., 0.5)),
+        ];
+        // barely moving in the slowly turning field at the center of the binary
+        let mut with_particle = binary.clone();
+        with_particle
+            .bodies
+            .push(body(0., 0., DVec3::new(1e-6, 0., 0.), DVec3::ZERO));
+
+        assert_eq!(
+            with_particle.advance(1.).substeps,
+            binary.advance(1.).substeps
+        );
+    }
+}
@@ -0,0 +1,279 @@
+use bevy::math::DVec3;
+
+use super::gravity::{gravitational_acceleration, Gravity};
+
+/// Past this depth, bodies are stored together in the same leaf instead of
+/// subdividing further, so coincident bodies cannot recurse forever.
+const MAX_DEPTH: usize = 32;
+
+/// A cubic cell of the octree.
+struct Node {
+    /// The center of the cell.
+    center: DVec3,
+    /// Half the length of the cell's edges.
+    half_size: f64,
+    /// The total mass of the bodies in the cell.
+    mass: f64,
+    /// The sum of the positions of the bodies in the cell, weighted by their masses.
+    weighted_position: DVec3,
+    /// The sum of the softening lengths of the bodies in the cell, weighted by their masses.
+    weighted_softening: f64,
+    /// The index of the first of the 8 contiguous children, if subdivided.
+    children: Option<usize>,
+    /// The bodies of the cell, if it is a leaf.
+    bodies: Vec<usize>,
+}
+
+impl Node {
+    fn new(center: DVec3, half_size: f64) -> Self {
+        Self {
+            center,
+            half_size,
+            mass: 0.,
+            weighted_position: DVec3::ZERO,
+            weighted_softening: 0.,
+            children: None,
+            bodies: Vec::new(),
+        }
+    }
+
+    /// The center of mass of the bodies in the cell.
+    fn center_of_mass(&self) -> DVec3 {
+        self.weighted_position / self.mass
+    }
+
+    /// The softening length of the cell, the mean of its bodies' weighted by their masses.
+    fn softening(&self) -> f64 {
+        self.weighted_softening / self.mass
+    }
+
+    /// The index (0..8) of the child cell containing `position`.
+    fn octant(&self, position: DVec3) -> usize {
+        (position.x >= self.center.x) as usize
+            | ((position.y >= self.center.y) as usize) << 1
+            | ((position.z >= self.center.z) as usize) << 2
+    }
+}
+
+/// An octree summarizing the mass distribution of the bodies, used to
+/// approximate the gravity of distant groups of bodies by their center of mass.
+pub struct Octree<'a> {
+    nodes: Vec<Node>,
+    positions: &'a [DVec3],
+    gravity: &'a Gravity<'a>,
+}
+
+impl<'a> Octree<'a> {
+    /// Builds the octree of the bodies at `positions`, leaving out the massless
+    /// ones which exert no gravity.
+    pub fn new(positions: &'a [DVec3], gravity: &'a Gravity<'a>) -> Self {
+        let inserted = |body: &usize| positions[*body].is_finite() && gravity.masses[*body] != 0.;
+        let (min, max) = (0..positions.len())
+            .filter(inserted)
+            .map(|body| positions[body])
+            .fold(
+                (DVec3::splat(f64::MAX), DVec3::splat(f64::MIN)),
+                |(min, max), p| (min.min(p), max.max(p)),
+            );
+        let (center, half_size) = if min.x <= max.x {
+            (
+                (min + max) / 2.,
+                ((max - min).max_element() / 2.).max(f64::EPSILON),
+            )
+        } else {
+            (DVec3::ZERO, 1.)
+        };
+
+        let mut octree = Self {
+            nodes: vec![Node::new(center, half_size)],
+            positions,
+            gravity,
+        };
+        for body in (0..positions.len()).filter(inserted) {
+            octree.insert(0, body, 0);
+        }
+        octree
+    }
+
+    /// Inserts `body` in the cell `node` or one of its descendants.
+    fn insert(&mut self, node: usize, body: usize, depth: usize) {
+        let position = self.positions[body];
+        let mass = self.gravity.masses[body];
+        self.nodes[node].mass += mass;
+        self.nodes[node].weighted_position += position * mass;
+        self.nodes[node].weighted_softening += self.gravity.softenings[body] * mass;
+
+        if self.nodes[node].children.is_none() {
+            if self.nodes[node].bodies.is_empty() || depth >= MAX_DEPTH {
+                self.nodes[node].bodies.push(body);
+                return;
+            }
+            self.subdivide(node);
+            for previous_body in std::mem::take(&mut self.nodes[node].bodies) {
+                self.insert_in_child(node, previous_body, depth);
+            }
+        }
+        self.insert_in_child(node, body, depth);
+    }
+
+    /// Inserts `body` in the child of `node` containing it.
+    fn insert_in_child(&mut self, node: usize, body: usize, depth: usize) {
+        let first_child = self.nodes[node].children.unwrap();
+        let child = first_child + self.nodes[node].octant(self.positions[body]);
+        self.insert(child, body, depth + 1);
+    }
+
+    /// Splits a leaf in 8 empty children.
+    fn subdivide(&mut self, node: usize) {
+        let Node {
+            center, half_size, ..
+        } = self.nodes[node];
+        let first_child = self.nodes.len();
+        for octant in 0..8 {
+            let offset = DVec3::new(
+                if octant & 1 != 0 { 1. } else { -1. },
+                if octant & 2 != 0 { 1. } else { -1. },
+                if octant & 4 != 0 { 1. } else { -1. },
+            ) * half_size
+                / 2.;
+            self.nodes.push(Node::new(center + offset, half_size / 2.));
+        }
+        self.nodes[node].children = Some(first_child);
+    }
+
+    /// Computes the acceleration of `body`, opening the cells whose apparent
+    /// size (edge length over distance) exceeds `opening_angle`.
+    pub fn acceleration(&self, body: usize, opening_angle: f64) -> DVec3 {
+        let position = self.positions[body];
+        let mut acceleration = DVec3::ZERO;
+        let mut stack = vec![0];
+
+        while let Some(node) = stack.pop() {
+            let node = &self.nodes[node];
+            if node.mass == 0. {
+                continue;
+            }
+            match node.children {
+                None => {
+                    for &other in node.bodies.iter().filter(|&&other| other != body) {
+                        acceleration += gravitational_acceleration(
+                            position,
+                            self.positions[other],
+                            self.gravity.masses[other],
+                            self.gravity.g,
+                            self.gravity.pair_softening(body, other),
+                        );
+                    }
+                }
+                Some(first_child) => {
+                    let center_of_mass = node.center_of_mass();
+                    let distance = position.distance(center_of_mass);
+                    let contains_body =
+                        (position - node.center).abs().max_element() <= node.half_size;
+                    if !contains_body && 2. * node.half_size < opening_angle * distance {
+                        // combined like `Gravity::pair_softening`, so that the
+                        // force does not jump when the cell is opened
+                        let softening = ((self.gravity.softenings[body].powi(2)
+                            + node.softening().powi(2))
+                            / 2.)
+                            .sqrt();
+                        acceleration += gravitational_acceleration(
+                            position,
+                            center_of_mass,
+                            node.mass,
+                            self.gravity.g,
+                            softening,
+                        );
+                    } else {
+                        stack.extend(first_child..first_child + 8);
+                    }
+                }
+            }
+        }
+        acceleration
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    use super::*;
+    use crate::simulation::gravity::ForceSolver;
+
+    /// `count` bodies of random masses spread in a cube of edge 200.
+    fn random_cloud(count: usize) -> (Vec<DVec3>, Vec<f64>) {
+        let mut rng = StdRng::seed_from_u64(0);
+        let positions = (0..count)
+            .map(|_| {
+                DVec3::new(
+                    rng.gen_range(-100.0..100.0),
+                    rng.gen_range(-100.0..100.0),
+                    rng.gen_range(-100.0..100.0),
+                )
+            })
+            .collect();
+        let masses = (0..count).map(|_| rng.gen_range(0.5..2.)).collect();
+        (positions, masses)
+    }
+
+    fn gravity<'a>(masses: &'a [f64], softenings: &'a [f64]) -> Gravity<'a> {
+        Gravity {
+            g: 1.,
+            masses,
+            softenings,
+            solver: ForceSolver::BarnesHut,
+            opening_angle: 0.2,
+            parallel: false,
+        }
+    }
+
+    #[test]
+    fn accelerations_match_pairwise_sum() {
+        let (positions, masses) = random_cloud(200);
+        let mut rng = StdRng::seed_from_u64(1);
+        let uniform = vec![0.1; masses.len()];
+        let varied = (0..masses.len())
+            .map(|_| rng.gen_range(0.0..40.0))
+            .collect::<Vec<_>>();
+
+        for softenings in [uniform, varied] {
+            let gravity = gravity(&masses, &softenings);
+            let attractors = gravity.attractors();
+            let octree = Octree::new(&positions, &gravity);
+
+            for body in 0..positions.len() {
+                let exact = gravity.pairwise_acceleration(body, &positions, &attractors);
+                let approximate = octree.acceleration(body, gravity.opening_angle);
+                let relative_error = exact.distance(approximate) / exact.length();
+                assert!(
+                    relative_error < 1e-2,
+                    "body {}: {} instead of {}",
+                    body,
+                    approximate,
+                    exact
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn massless_bodies_are_left_out() {
+        let (positions, mut masses) = random_cloud(50);
+        for mass in masses.iter_mut().step_by(2) {
+            *mass = 0.;
+        }
+        let softenings = vec![0.1; masses.len()];
+        let gravity = gravity(&masses, &softenings);
+        let octree = Octree::new(&positions, &gravity);
+
+        let mut inserted = octree
+            .nodes
+            .iter()
+            .flat_map(|node| node.bodies.iter().copied())
+            .collect::<Vec<_>>();
+        inserted.sort_unstable();
+        assert_eq!(inserted, gravity.attractors());
+        assert_eq!(octree.nodes[0].mass, masses.iter().sum::<f64>());
+    }
+}
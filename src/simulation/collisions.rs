@@ -0,0 +1,367 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use bevy::math::DVec3;
+use serde::{Deserialize, Serialize};
+
+use super::Body;
+
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+/// What happens when two bodies touch.
+pub enum CollisionResponse {
+    /// Perfectly inelastic collision: the lighter body is absorbed by the heavier one,
+    /// conserving mass and momentum.
+    #[default]
+    Merge,
+    /// Perfectly elastic collision: the bodies bounce off each other.
+    Bounce,
+    /// Bodies pass through each other.
+    Ignore,
+}
+
+impl CollisionResponse {
+    /// Every available response, in the order they are shown in the UI.
+    pub const ALL: [CollisionResponse; 3] = [
+        CollisionResponse::Merge,
+        CollisionResponse::Bounce,
+        CollisionResponse::Ignore,
+    ];
+}
+
+impl fmt::Display for CollisionResponse {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            CollisionResponse::Merge => "Merge",
+            CollisionResponse::Bounce => "Bounce",
+            CollisionResponse::Ignore => "Ignore",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// A collision between two bodies during a simulation step.
+///
+/// Indices refer to the bodies as they were at the start of the step.
+pub struct Collision {
+    /// The surviving body when merging, the first body otherwise.
+    pub first: usize,
+    /// The absorbed body when merging, the second body otherwise.
+    pub second: usize,
+    /// The mass of the first body before the collision.
+    pub first_mass: f64,
+    /// The mass of the second body before the collision.
+    pub second_mass: f64,
+    /// The speed at which the bodies approached each other.
+    pub relative_speed: f64,
+    /// How the collision was resolved.
+    pub response: CollisionResponse,
+}
+
+impl Collision {
+    /// The index of the body that disappeared in this collision, if any.
+    pub fn absorbed(&self) -> Option<usize> {
+        (self.response == CollisionResponse::Merge).then_some(self.second)
+    }
+}
+
+/// Removes the items matching bodies absorbed in `collisions`, so that a
+/// collection indexed like the bodies keeps matching them after a step.
+pub fn retain_survivors<T>(items: &mut Vec<T>, collisions: &[Collision]) {
+    let mut index = 0;
+    items.retain(|_| {
+        let absorbed = collisions.iter().any(|c| c.absorbed() == Some(index));
+        index += 1;
+        !absorbed
+    });
+}
+
+/// Detects overlapping bodies and resolves the collisions with `response`.
+///
+/// Massless bodies only collide with massive ones, so that clouds of test
+/// particles stay cheap.
+pub fn resolve_collisions(bodies: &mut Vec<Body>, response: CollisionResponse) -> Vec<Collision> {
+    let mut collisions = Vec::new();
+    if response == CollisionResponse::Ignore {
+        return collisions;
+    }
+
+    let grid = Grid::new(bodies);
+    let mut absorbed = vec![false; bodies.len()];
+    for i in 0..bodies.len() {
+        for j in grid.candidates(bodies, i) {
+            if absorbed[i] || absorbed[j] || (bodies[i].mass == 0. && bodies[j].mass == 0.) {
+                continue;
+            }
+            let (i, j) = (i.min(j), i.max(j));
+            let separation = bodies[j].position - bodies[i].position;
+            let distance = separation.length();
+            if distance >= bodies[i].radius + bodies[j].radius {
+                continue;
+            }
+
+            let normal = separation.try_normalize().unwrap_or(DVec3::X);
+            // positive when the bodies are getting closer
+            let approach_speed = (bodies[i].velocity - bodies[j].velocity).dot(normal);
+
+            match response {
+                CollisionResponse::Merge => {
+                    let (survivor, victim) = if bodies[j].mass > bodies[i].mass {
+                        (j, i)
+                    } else {
+                        (i, j)
+                    };
+                    collisions.push(Collision {
+                        first: survivor,
+                        second: victim,
+                        first_mass: bodies[survivor].mass,
+                        second_mass: bodies[victim].mass,
+                        relative_speed: approach_speed.abs(),
+                        response,
+                    });
+                    let victim_body = bodies[victim].clone();
+                    merge(&mut bodies[survivor], &victim_body);
+                    absorbed[victim] = true;
+                }
+                CollisionResponse::Bounce => {
+                    if approach_speed <= 0. {
+                        // already separating
+                        continue;
+                    }
+                    collisions.push(Collision {
+                        first: i,
+                        second: j,
+                        first_mass: bodies[i].mass,
+                        second_mass: bodies[j].mass,
+                        relative_speed: approach_speed,
+                        response,
+                    });
+                    let (first, second) = bodies.split_at_mut(j);
+                    bounce(&mut first[i], &mut second[0], normal, approach_speed);
+                }
+                CollisionResponse::Ignore => unreachable!(),
+            }
+        }
+    }
+
+    let mut index = 0;
+    bodies.retain(|_| {
+        index += 1;
+        !absorbed[index - 1]
+    });
+    collisions
+}
+
+/// A uniform grid of the bodies with a radius, the broad phase finding the
+/// bodies which may overlap without testing every pair.
+///
+/// Bodies of radius 0, such as the particles of the clouds, are left out: they
+/// can only hit a body with a radius, which they look up around them.
+struct Grid {
+    /// The edge of the cells, twice the largest radius.
+    cell_size: f64,
+    /// The largest radius.
+    max_radius: f64,
+    /// The bodies in each non-empty cell, in increasing order.
+    cells: HashMap<(i64, i64, i64), Vec<usize>>,
+}
+
+impl Grid {
+    fn new(bodies: &[Body]) -> Self {
+        let max_radius = bodies
+            .iter()
+            .filter(|body| body.position.is_finite())
+            .map(|body| body.radius)
+            .fold(0., f64::max);
+        let mut grid = Self {
+            cell_size: 2. * max_radius,
+            max_radius,
+            cells: HashMap::new(),
+        };
+        if max_radius > 0. {
+            for (index, body) in bodies.iter().enumerate() {
+                if body.radius > 0. && body.position.is_finite() {
+                    let cell = grid.cell(body.position);
+                    grid.cells.entry(cell).or_default().push(index);
+                }
+            }
+        }
+        grid
+    }
+
+    /// The cell containing `position`.
+    fn cell(&self, position: DVec3) -> (i64, i64, i64) {
+        let cell = (position / self.cell_size).floor();
+        (cell.x as i64, cell.y as i64, cell.z as i64)
+    }
+
+    /// The bodies which may overlap `body`, in increasing order, each pair
+    /// being given once: by the lowest index, or by the body left out of the grid.
+    fn candidates(&self, bodies: &[Body], body: usize) -> Vec<usize> {
+        let Body {
+            position, radius, ..
+        } = bodies[body];
+        if self.cells.is_empty() || !position.is_finite() {
+            return Vec::new();
+        }
+        let in_grid = radius > 0.;
+        let reach = ((radius + self.max_radius) / self.cell_size).ceil() as i64;
+        let (x, y, z) = self.cell(position);
+        let mut candidates = Vec::new();
+        for dx in -reach..=reach {
+            for dy in -reach..=reach {
+                for dz in -reach..=reach {
+                    if let Some(cell) = self.cells.get(&(x + dx, y + dy, z + dz)) {
+                        candidates.extend(
+                            cell.iter()
+                                .copied()
+                                .filter(|&other| other > body || !in_grid && other != body),
+                        );
+                    }
+                }
+            }
+        }
+        candidates.sort_unstable();
+        candidates
+    }
+}
+
+/// Merges `other` into `body`, conserving mass, momentum and volume.
+fn merge(body: &mut Body, other: &Body) {
+    let mass = body.mass + other.mass;
+    if mass > 0. {
+        body.position = (body.position * body.mass + other.position * other.mass) / mass;
+        body.velocity = (body.velocity * body.mass + other.velocity * other.mass) / mass;
+    }
+    body.mass = mass;
+    body.radius = (body.radius.powi(3) + other.radius.powi(3)).cbrt();
+}
+
+/// Applies an elastic impulse along `normal` (pointing from `first` to `second`)
+/// and pushes the bodies apart so they no longer overlap.
+fn bounce(first: &mut Body, second: &mut Body, normal: DVec3, approach_speed: f64) {
+    let inverse_masses = inverse_mass(first.mass) + inverse_mass(second.mass);
+    let first_share = inverse_mass(first.mass) / inverse_masses;
+    let second_share = inverse_mass(second.mass) / inverse_masses;
+
+    first.velocity -= 2. * approach_speed * first_share * normal;
+    second.velocity += 2. * approach_speed * second_share * normal;
+
+    let overlap = first.radius + second.radius - first.position.distance(second.position);
+    first.position -= overlap * first_share * normal;
+    second.position += overlap * second_share * normal;
+}
+
+/// The inverse of a mass, massless bodies being treated as infinitely light.
+fn inverse_mass(mass: f64) -> f64 {
+    1. / mass.max(f64::MIN_POSITIVE)
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    use super::*;
+
+    fn body(mass: f64, radius: f64, position: DVec3, velocity: DVec3) -> Body {
+        Body {
+            mass,
+            radius,
+            position,
+            velocity,
+            softening: None,
+            acceleration: DVec3::ZERO,
+        }
+    }
+
+    fn momentum(bodies: &[&Body]) -> DVec3 {
+        bodies.iter().map(|body| body.velocity * body.mass).sum()
+    }
+
+    fn kinetic_energy(bodies: &[&Body]) -> f64 {
+        bodies
+            .iter()
+            .map(|body| body.mass * body.velocity.length_squared() / 2.)
+            .sum()
+    }
+
+    #[test]
+    fn merges_conserve_mass_momentum_and_the_center_of_mass() {
+        let heavy = body(3., 2., DVec3::new(0., 0., 0.), DVec3::new(1., 0., 0.));
+        let light = body(1., 1., DVec3::new(2., 0., 1.), DVec3::new(-1., 2., 0.));
+        let mut merged = heavy.clone();
+        merge(&mut merged, &light);
+
+        assert_eq!(merged.mass, 4.);
+        assert!((momentum(&[&merged]) - momentum(&[&heavy, &light])).length() < 1e-12);
+        assert!(merged.position.distance(DVec3::new(0.5, 0., 0.25)) < 1e-12);
+        assert!((merged.radius.powi(3) - 9.).abs() < 1e-12);
+    }
+
+    #[test]
+    fn bounces_conserve_momentum_and_kinetic_energy() {
+        let mut first = body(2., 1., DVec3::new(0., 0., 0.), DVec3::new(1., 0.5, 0.));
+        let mut second = body(1., 1., DVec3::new(1.5, 0.5, 0.), DVec3::new(-2., 0., 1.));
+        let (momentum_before, energy_before) = (
+            momentum(&[&first, &second]),
+            kinetic_energy(&[&first, &second]),
+        );
+
+        let normal = (second.position - first.position).normalize();
+        let approach_speed = (first.velocity - second.velocity).dot(normal);
+        assert!(approach_speed > 0.);
+        bounce(&mut first, &mut second, normal, approach_speed);
+
+        assert!((momentum(&[&first, &second]) - momentum_before).length() < 1e-12);
+        assert!((kinetic_energy(&[&first, &second]) - energy_before).abs() < 1e-12);
+        // the bodies separate and no longer overlap
+        assert!((first.velocity - second.velocity).dot(normal) < 0.);
+        assert!(first.position.distance(second.position) >= 2. - 1e-12);
+    }
+
+    #[test]
+    fn the_grid_gives_every_overlapping_pair_once() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let bodies = (0..500)
+            .map(|index| {
+                let position = DVec3::new(
+                    rng.gen_range(-50.0..50.0),
+                    rng.gen_range(-50.0..50.0),
+                    rng.gen_range(-50.0..50.0),
+                );
+                // bodies, massless bodies with a radius and particles
+                let (mass, radius) = match index % 3 {
+                    0 => (1., rng.gen_range(0.5..4.)),
+                    1 => (0., rng.gen_range(0.5..2.)),
+                    _ => (0., 0.),
+                };
+                body(mass, radius, position, DVec3::ZERO)
+            })
+            .collect::<Vec<_>>();
+        let grid = Grid::new(&bodies);
+
+        let mut found = (0..bodies.len())
+            .flat_map(|i| {
+                grid.candidates(&bodies, i)
+                    .into_iter()
+                    .map(move |j| (i.min(j), i.max(j)))
+            })
+            .collect::<Vec<_>>();
+        found.sort_unstable();
+        let mut overlapping = Vec::new();
+        for i in 0..bodies.len() {
+            for j in (i + 1)..bodies.len() {
+                if bodies[i].position.distance(bodies[j].position)
+                    < bodies[i].radius + bodies[j].radius
+                {
+                    overlapping.push((i, j));
+                }
+            }
+        }
+        assert!(!overlapping.is_empty());
+        for pair in &overlapping {
+            let count = found.iter().filter(|&found| found == pair).count();
+            assert_eq!(count, 1, "{:?} was given {} times", pair, count);
+        }
+    }
+}
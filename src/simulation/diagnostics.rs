@@ -0,0 +1,189 @@
+use bevy::math::DVec3;
+
+use super::Simulation;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+/// The quantities an isolated N-body system conserves, used to check that the
+/// simulation behaves physically.
+pub struct Diagnostics {
+    /// The total kinetic energy.
+    pub kinetic_energy: f64,
+    /// The total gravitational potential energy.
+    pub potential_energy: f64,
+    /// The total linear momentum.
+    pub momentum: DVec3,
+    /// The total angular momentum about the origin.
+    pub angular_momentum: DVec3,
+    /// The center of mass of the bodies.
+    pub center_of_mass: DVec3,
+    /// The sum of the norms of the bodies' momenta, the scale momentum drifts
+    /// are measured against since the total momentum is often zero.
+    pub momentum_scale: f64,
+    /// The sum of the norms of the bodies' angular momenta, the scale angular
+    /// momentum drifts are measured against.
+    pub angular_momentum_scale: f64,
+}
+
+impl Diagnostics {
+    /// The first line of a CSV file of diagnostics, see `csv_row`.
+    pub const CSV_HEADER: &'static str = "time,kinetic_energy,potential_energy,total_energy,\
+momentum_x,momentum_y,momentum_z,angular_momentum_x,angular_momentum_y,angular_momentum_z,\
+center_of_mass_x,center_of_mass_y,center_of_mass_z";
+
+    /// The total mechanical energy.
+    pub fn total_energy(&self) -> f64 {
+        self.kinetic_energy + self.potential_energy
+    }
+
+    /// How much the total energy drifted since `initial`, relative to it.
+    pub fn energy_drift(&self, initial: &Diagnostics) -> f64 {
+        // a system with no net energy still has kinetic and potential energy to compare to
+        let scale = if initial.total_energy() == 0. {
+            initial.kinetic_energy - initial.potential_energy
+        } else {
+            initial.total_energy().abs()
+        };
+        relative_drift(self.total_energy() - initial.total_energy(), scale)
+    }
+
+    /// How much the total momentum drifted since `initial`, relative to the
+    /// momenta of the bodies.
+    pub fn momentum_drift(&self, initial: &Diagnostics) -> f64 {
+        relative_drift(
+            self.momentum.distance(initial.momentum),
+            initial.momentum_scale,
+        )
+    }
+
+    /// How much the total angular momentum drifted since `initial`, relative
+    /// to the angular momenta of the bodies.
+    pub fn angular_momentum_drift(&self, initial: &Diagnostics) -> f64 {
+        relative_drift(
+            self.angular_momentum.distance(initial.angular_momentum),
+            initial.angular_momentum_scale,
+        )
+    }
+
+    /// Formats the diagnostics at the simulated `time` as a line of CSV.
+    pub fn csv_row(&self, time: f64) -> String {
+        let vectors = [self.momentum, self.angular_momentum, self.center_of_mass]
+            .iter()
+            .flat_map(|v| v.to_array())
+            .map(|x| x.to_string())
+            .collect::<Vec<_>>()
+            .join(",");
+        format!(
+            "{},{},{},{},{}",
+            time,
+            self.kinetic_energy,
+            self.potential_energy,
+            self.total_energy(),
+            vectors
+        )
+    }
+}
+
+/// The norm of a change relative to `scale`, or the norm itself when there is
+/// nothing to compare it to.
+fn relative_drift(change: f64, scale: f64) -> f64 {
+    if scale > 0. {
+        change.abs() / scale
+    } else {
+        change.abs()
+    }
+}
+
+impl Simulation {
+    /// Computes the conserved quantities of the bodies in their current state.
+    pub fn diagnostics(&self) -> Diagnostics {
+        let mut diagnostics = Diagnostics::default();
+        let mut total_mass = 0.;
+        for body in &self.bodies {
+            let momentum = body.mass * body.velocity;
+            let angular_momentum = body.position.cross(momentum);
+            diagnostics.kinetic_energy += 0.5 * body.mass * body.velocity.length_squared();
+            diagnostics.momentum += momentum;
+            diagnostics.angular_momentum += angular_momentum;
+            diagnostics.center_of_mass += body.mass * body.position;
+            diagnostics.momentum_scale += momentum.length();
+            diagnostics.angular_momentum_scale += angular_momentum.length();
+            total_mass += body.mass;
+        }
+        if total_mass > 0. {
+            diagnostics.center_of_mass /= total_mass;
+        }
+
+        let positions = self.bodies.iter().map(|b| b.position).collect::<Vec<_>>();
+        let (masses, softenings) = self.masses_and_softenings();
+        diagnostics.potential_energy = self
+            .gravity(&masses, &softenings)
+            .potential_energy(&positions);
+        diagnostics
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simulation::{integrators::IntegratorKind, physics_config::PhysicsConfig, Body};
+
+    /// Two bodies whose conserved quantities are easily computed by hand.
+    fn two_bodies() -> Simulation {
+        let physics = PhysicsConfig {
+            g: 1.,
+            softening: 0.,
+            parallel: false,
+            ..Default::default()
+        };
+        let mut simulation = Simulation::new(physics, IntegratorKind::default());
+        let body = |mass, position, velocity| Body {
+            mass,
+            radius: 0.1,
+            position,
+            velocity,
+            softening: None,
+            acceleration: DVec3::ZERO,
+        };
+        simulation.bodies = vec![
+            body(2., DVec3::new(1., 0., 0.), DVec3::new(0., 0., 1.)),
+            body(1., DVec3::new(-1., 0., 0.), DVec3::new(0., 3., 0.)),
+        ];
+        simulation
+    }
+
+    #[test]
+    fn two_bodies_have_the_expected_diagnostics() {
+        let diagnostics = two_bodies().diagnostics();
+
+        assert_eq!(diagnostics.kinetic_energy, 0.5 * 2. + 0.5 * 9.);
+        assert_eq!(diagnostics.potential_energy, -2. * 1. / 2.);
+        assert_eq!(diagnostics.total_energy(), 4.5);
+        assert_eq!(diagnostics.momentum, DVec3::new(0., 3., 2.));
+        assert_eq!(diagnostics.angular_momentum, DVec3::new(0., -2., -3.));
+        assert_eq!(diagnostics.center_of_mass, DVec3::new(1. / 3., 0., 0.));
+        assert_eq!(diagnostics.momentum_scale, 5.);
+        assert_eq!(diagnostics.angular_momentum_scale, 5.);
+    }
+
+    #[test]
+    fn unchanged_states_do_not_drift() {
+        let diagnostics = two_bodies().diagnostics();
+
+        assert_eq!(diagnostics.energy_drift(&diagnostics), 0.);
+        assert_eq!(diagnostics.momentum_drift(&diagnostics), 0.);
+        assert_eq!(diagnostics.angular_momentum_drift(&diagnostics), 0.);
+    }
+
+    #[test]
+    fn drifts_from_nothing_are_finite() {
+        let initial = Diagnostics::default();
+        let current = two_bodies().diagnostics();
+
+        assert_eq!(initial.energy_drift(&initial), 0.);
+        assert_eq!(initial.momentum_drift(&initial), 0.);
+        assert_eq!(initial.angular_momentum_drift(&initial), 0.);
+        assert_eq!(current.energy_drift(&initial), 4.5);
+        assert_eq!(current.momentum_drift(&initial), 13f64.sqrt());
+        assert_eq!(current.angular_momentum_drift(&initial), 13f64.sqrt());
+    }
+}
@@ -0,0 +1,188 @@
+use std::{fmt, str::FromStr};
+
+use bevy::{
+    math::DVec3,
+    tasks::{ComputeTaskPool, ParallelSlice, TaskPool},
+};
+use serde::{Deserialize, Serialize};
+
+use super::barnes_hut::Octree;
+
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+/// How the gravitational forces are evaluated.
+pub enum ForceSolver {
+    /// Exact sum over every pair of bodies, in O(n²).
+    #[default]
+    Pairwise,
+    /// Barnes-Hut octree approximation, in O(n log n).
+    BarnesHut,
+}
+
+impl ForceSolver {
+    /// Every available solver, in the order they are shown in the UI.
+    pub const ALL: [ForceSolver; 2] = [ForceSolver::Pairwise, ForceSolver::BarnesHut];
+}
+
+impl fmt::Display for ForceSolver {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            ForceSolver::Pairwise => "Exact pairwise",
+            ForceSolver::BarnesHut => "Barnes-Hut",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl FromStr for ForceSolver {
+    type Err = String;
+
+    /// Parses the short name of a solver, as used on the command line.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "pairwise" => Ok(ForceSolver::Pairwise),
+            "barnes-hut" => Ok(ForceSolver::BarnesHut),
+            _ => Err(format!(
+                "Unknown force solver {} (expected pairwise or barnes-hut)",
+                s
+            )),
+        }
+    }
+}
+
+/// Below this number of bodies, spawning tasks costs more than it saves.
+const PARALLEL_THRESHOLD: usize = 64;
+
+/// The gravitational acceleration applied to a body at `position_1` by a body
+/// of mass `mass_2` at `position_2`, softened by the Plummer length `softening`.
+///
+/// Coincident bodies without softening exert no force on each other instead of
+/// producing an infinite acceleration.
+pub fn gravitational_acceleration(
+    position_1: DVec3,
+    position_2: DVec3,
+    mass_2: f64,
+    g: f64,
+    softening: f64,
+) -> DVec3 {
+    let separation = position_2 - position_1;
+    let softened_squared_dist = separation.length_squared() + softening * softening;
+    if softened_squared_dist == 0. {
+        return DVec3::ZERO;
+    }
+    separation * g * mass_2 / (softened_squared_dist * softened_squared_dist.sqrt())
+}
+
+/// The gravitational interactions between a set of bodies.
+pub struct Gravity<'a> {
+    /// The gravitational constant.
+    pub g: f64,
+    /// The bodies' masses.
+    pub masses: &'a [f64],
+    /// The bodies' softening lengths.
+    pub softenings: &'a [f64],
+    /// How the forces are evaluated.
+    pub solver: ForceSolver,
+    /// The Barnes-Hut opening angle θ: larger is faster but less accurate.
+    pub opening_angle: f64,
+    /// Whether the bodies are spread over the `ComputeTaskPool` threads.
+    pub parallel: bool,
+}
+
+impl Gravity<'_> {
+    /// The softening length used between bodies `i` and `j`, so that the
+    /// force stays symmetric when they have different softening lengths.
+    pub fn pair_softening(&self, i: usize, j: usize) -> f64 {
+        ((self.softenings[i].powi(2) + self.softenings[j].powi(2)) / 2.).sqrt()
+    }
+
+    /// The indices of the bodies exerting gravity, massless bodies only feeling it.
+    pub fn attractors(&self) -> Vec<usize> {
+        (0..self.masses.len())
+            .filter(|&body| self.masses[body] != 0.)
+            .collect()
+    }
+
+    /// Computes the acceleration of every body due to the gravity of all the others.
+    pub fn accelerations(&self, positions: &[DVec3]) -> Vec<DVec3> {
+        let octree = (self.solver == ForceSolver::BarnesHut).then(|| Octree::new(positions, self));
+        let attractors = self.attractors();
+        let acceleration = |body: usize| match &octree {
+            Some(octree) => octree.acceleration(body, self.opening_angle),
+            None => self.pairwise_acceleration(body, positions, &attractors),
+        };
+
+        if self.parallel && positions.len() >= PARALLEL_THRESHOLD {
+            let bodies = (0..positions.len()).collect::<Vec<_>>();
+            let task_pool = ComputeTaskPool::get_or_init(TaskPool::default);
+            bodies
+                .par_splat_map(task_pool, None, |chunk| {
+                    chunk
+                        .iter()
+                        .map(|&body| acceleration(body))
+                        .collect::<Vec<_>>()
+                })
+                .into_iter()
+                .flatten()
+                .collect()
+        } else {
+            (0..positions.len()).map(acceleration).collect()
+        }
+    }
+
+    /// Computes the total gravitational potential energy of the bodies, always
+    /// summing exactly over every pair whatever the solver.
+    pub fn potential_energy(&self, positions: &[DVec3]) -> f64 {
+        let attractors = self.attractors();
+        let body_energy = |index: usize| {
+            let body = attractors[index];
+            attractors[(index + 1)..]
+                .iter()
+                .map(|&other| {
+                    let softening = self.pair_softening(body, other);
+                    let softened_squared_dist =
+                        positions[body].distance_squared(positions[other]) + softening * softening;
+                    if softened_squared_dist == 0. {
+                        return 0.;
+                    }
+                    -self.g * self.masses[body] * self.masses[other] / softened_squared_dist.sqrt()
+                })
+                .sum::<f64>()
+        };
+
+        if self.parallel && attractors.len() >= PARALLEL_THRESHOLD {
+            let indices = (0..attractors.len()).collect::<Vec<_>>();
+            let task_pool = ComputeTaskPool::get_or_init(TaskPool::default);
+            indices
+                .par_splat_map(task_pool, None, |chunk| {
+                    chunk.iter().map(|&index| body_energy(index)).sum::<f64>()
+                })
+                .into_iter()
+                .sum()
+        } else {
+            (0..attractors.len()).map(body_energy).sum()
+        }
+    }
+
+    /// Computes the exact acceleration of `body` by summing over every attractor.
+    pub(super) fn pairwise_acceleration(
+        &self,
+        body: usize,
+        positions: &[DVec3],
+        attractors: &[usize],
+    ) -> DVec3 {
+        let mut acceleration = DVec3::ZERO;
+        for &other in attractors {
+            if other == body {
+                continue;
+            }
+            acceleration += gravitational_acceleration(
+                positions[body],
+                positions[other],
+                self.masses[other],
+                self.g,
+                self.pair_softening(body, other),
+            );
+        }
+        acceleration
+    }
+}
@@ -0,0 +1,321 @@
+use std::{fmt, str::FromStr};
+
+use bevy::{math::DVec3, prelude::*};
+use serde::{Deserialize, Serialize};
+
+use super::gravity::Gravity;
+
+/// A numerical scheme advancing the N-body system by one time step.
+pub trait Integrator: Send + Sync {
+    /// Advances `positions` and `velocities` in place by `dt` under `gravity`.
+    ///
+    /// Returns the accelerations of the first force evaluation of the step.
+    fn step(
+        &self,
+        positions: &mut [DVec3],
+        velocities: &mut [DVec3],
+        gravity: &Gravity,
+        dt: f64,
+    ) -> Vec<DVec3>;
+}
+
+/// First order symplectic Euler: kick the velocities, then drift the positions.
+pub struct SemiImplicitEuler;
+
+impl Integrator for SemiImplicitEuler {
+    fn step(
+        &self,
+        positions: &mut [DVec3],
+        velocities: &mut [DVec3],
+        gravity: &Gravity,
+        dt: f64,
+    ) -> Vec<DVec3> {
+        let accelerations = gravity.accelerations(positions);
+        for i in 0..positions.len() {
+            velocities[i] += accelerations[i] * dt;
+            positions[i] += velocities[i] * dt;
+        }
+        accelerations
+    }
+}
+
+/// Second order symplectic leapfrog in its drift-kick-drift form.
+pub struct Leapfrog;
+
+impl Integrator for Leapfrog {
+    fn step(
+        &self,
+        positions: &mut [DVec3],
+        velocities: &mut [DVec3],
+        gravity: &Gravity,
+        dt: f64,
+    ) -> Vec<DVec3> {
+        for (position, velocity) in positions.iter_mut().zip(velocities.iter()) {
+            *position += *velocity * dt / 2.;
+        }
+        let accelerations = gravity.accelerations(positions);
+        for i in 0..positions.len() {
+            velocities[i] += accelerations[i] * dt;
+            positions[i] += velocities[i] * dt / 2.;
+        }
+        accelerations
+    }
+}
+
+/// Second order symplectic velocity Verlet (kick-drift-kick).
+pub struct VelocityVerlet;
+
+impl Integrator for VelocityVerlet {
+    fn step(
+        &self,
+        positions: &mut [DVec3],
+        velocities: &mut [DVec3],
+        gravity: &Gravity,
+        dt: f64,
+    ) -> Vec<DVec3> {
+        let initial_accelerations = gravity.accelerations(positions);
+        for i in 0..positions.len() {
+            velocities[i] += initial_accelerations[i] * dt / 2.;
+            positions[i] += velocities[i] * dt;
+        }
+        let accelerations = gravity.accelerations(positions);
+        for i in 0..positions.len() {
+            velocities[i] += accelerations[i] * dt / 2.;
+        }
+        initial_accelerations
+    }
+}
+
+/// Classic fourth order Runge-Kutta. Very accurate on short runs but not symplectic,
+/// so the energy slowly drifts over long runs.
+pub struct Rk4;
+
+impl Integrator for Rk4 {
+    fn step(
+        &self,
+        positions: &mut [DVec3],
+        velocities: &mut [DVec3],
+        gravity: &Gravity,
+        dt: f64,
+    ) -> Vec<DVec3> {
+        let offset = |base: &[DVec3], derivative: &[DVec3], factor: f64| {
+            base.iter()
+                .zip(derivative)
+                .map(|(b, d)| *b + *d * factor)
+                .collect::<Vec<_>>()
+        };
+
+        let k1_x = velocities.to_vec();
+        let k1_v = gravity.accelerations(positions);
+
+        let k2_x = offset(velocities, &k1_v, dt / 2.);
+        let k2_v = gravity.accelerations(&offset(positions, &k1_x, dt / 2.));
+
+        let k3_x = offset(velocities, &k2_v, dt / 2.);
+        let k3_v = gravity.accelerations(&offset(positions, &k2_x, dt / 2.));
+
+        let k4_x = offset(velocities, &k3_v, dt);
+        let k4_v = gravity.accelerations(&offset(positions, &k3_x, dt));
+
+        for i in 0..positions.len() {
+            positions[i] += dt / 6. * (k1_x[i] + 2. * k2_x[i] + 2. * k3_x[i] + k4_x[i]);
+            velocities[i] += dt / 6. * (k1_v[i] + 2. * k2_v[i] + 2. * k3_v[i] + k4_v[i]);
+        }
+        k1_v
+    }
+}
+
+#[derive(Resource, Default, Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+/// The integrator used to advance the simulation.
+pub enum IntegratorKind {
+    SemiImplicitEuler,
+    Leapfrog,
+    #[default]
+    VelocityVerlet,
+    Rk4,
+}
+
+impl IntegratorKind {
+    /// Every available integrator, in the order they are shown in the UI.
+    pub const ALL: [IntegratorKind; 4] = [
+        IntegratorKind::SemiImplicitEuler,
+        IntegratorKind::Leapfrog,
+        IntegratorKind::VelocityVerlet,
+        IntegratorKind::Rk4,
+    ];
+
+    /// Returns the integrator implementation for this kind.
+    pub fn integrator(self) -> &'static dyn Integrator {
+        match self {
+            IntegratorKind::SemiImplicitEuler => &SemiImplicitEuler,
+            IntegratorKind::Leapfrog => &Leapfrog,
+            IntegratorKind::VelocityVerlet => &VelocityVerlet,
+            IntegratorKind::Rk4 => &Rk4,
+        }
+    }
+
+    /// The order of the integrator: its global error scales as `dt^order`.
+    pub fn order(self) -> u32 {
+        match self {
+            IntegratorKind::SemiImplicitEuler => 1,
+            IntegratorKind::Leapfrog | IntegratorKind::VelocityVerlet => 2,
+            IntegratorKind::Rk4 => 4,
+        }
+    }
+}
+
+impl fmt::Display for IntegratorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            IntegratorKind::SemiImplicitEuler => "Semi-implicit Euler",
+            IntegratorKind::Leapfrog => "Leapfrog",
+            IntegratorKind::VelocityVerlet => "Velocity Verlet",
+            IntegratorKind::Rk4 => "Runge-Kutta 4",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl FromStr for IntegratorKind {
+    type Err = String;
+
+    /// Parses the short name of an integrator, as used on the command line,
+    /// or its displayed name.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let name = s.to_lowercase();
+        match name.as_str() {
+            "euler" => Ok(IntegratorKind::SemiImplicitEuler),
+            "leapfrog" => Ok(IntegratorKind::Leapfrog),
+            "verlet" => Ok(IntegratorKind::VelocityVerlet),
+            "rk4" => Ok(IntegratorKind::Rk4),
+            _ => IntegratorKind::ALL
+                .into_iter()
+                .find(|kind| kind.to_string().to_lowercase() == name)
+                .ok_or_else(|| {
+                    format!(
+                        "Unknown integrator {} (expected euler, leapfrog, verlet or rk4)",
+                        s
+                    )
+                }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f64::consts::TAU;
+
+    use super::*;
+    use crate::simulation::gravity::ForceSolver;
+
+    const MASSES: [f64; 2] = [1., 1e-3];
+    const SOFTENINGS: [f64; 2] = [0., 0.];
+
+    fn gravity() -> Gravity<'static> {
+        Gravity {
+            g: 1.,
+            masses: &MASSES,
+            softenings: &SOFTENINGS,
+            solver: ForceSolver::Pairwise,
+            opening_angle: 0.,
+            parallel: false,
+        }
+    }
+
+    /// The angular velocity of the circular orbit of unit radius.
+    fn angular_velocity() -> f64 {
+        (MASSES[0] + MASSES[1]).sqrt()
+    }
+
+    /// The positions and velocities of the two bodies on a circular orbit of
+    /// unit radius around their center of mass, at `time`.
+    fn circular_orbit(time: f64) -> ([DVec3; 2], [DVec3; 2]) {
+        let total = MASSES[0] + MASSES[1];
+        let angle = angular_velocity() * time;
+        let direction = DVec3::new(angle.cos(), 0., angle.sin());
+        let tangent = DVec3::new(-angle.sin(), 0., angle.cos());
+        let shares = [-MASSES[1] / total, MASSES[0] / total];
+        (
+            shares.map(|share| direction * share),
+            shares.map(|share| tangent * share * angular_velocity()),
+        )
+    }
+
+    fn energy(positions: &[DVec3], velocities: &[DVec3]) -> f64 {
+        let kinetic = MASSES
+            .iter()
+            .zip(velocities)
+            .map(|(mass, velocity)| mass * velocity.length_squared() / 2.)
+            .sum::<f64>();
+        kinetic - MASSES[0] * MASSES[1] / positions[0].distance(positions[1])
+    }
+
+    /// Integrates the orbit for `duration` in `steps` steps, returning the
+    /// final positions and velocities.
+    fn integrate(kind: IntegratorKind, duration: f64, steps: usize) -> ([DVec3; 2], [DVec3; 2]) {
+        let (mut positions, mut velocities) = circular_orbit(0.);
+        for _ in 0..steps {
+            kind.integrator().step(
+                &mut positions,
+                &mut velocities,
+                &gravity(),
+                duration / steps as f64,
+            );
+        }
+        (positions, velocities)
+    }
+
+    #[test]
+    fn circular_orbits_keep_their_energy() {
+        let (positions, velocities) = circular_orbit(0.);
+        let initial = energy(&positions, &velocities);
+        let period = TAU / angular_velocity();
+        for (kind, bound) in [
+            (IntegratorKind::SemiImplicitEuler, 1e-8),
+            (IntegratorKind::Leapfrog, 1e-12),
+            (IntegratorKind::VelocityVerlet, 1e-12),
+            (IntegratorKind::Rk4, 1e-8),
+        ] {
+            let (positions, velocities) = integrate(kind, 10. * period, 5000);
+            let drift = ((energy(&positions, &velocities) - initial) / initial).abs();
+            assert!(drift < bound, "{}: energy drifted by {:e}", kind, drift);
+        }
+    }
+
+    #[test]
+    fn halving_the_time_step_divides_the_error_by_two_to_the_order() {
+        let duration = TAU / angular_velocity() / 4.;
+        let (exact, _) = circular_orbit(duration);
+        let error = |kind, steps| {
+            let (positions, _) = integrate(kind, duration, steps);
+            positions[1].distance(exact[1])
+        };
+        for kind in IntegratorKind::ALL {
+            let ratio = error(kind, 100) / error(kind, 200);
+            let expected = 2f64.powi(kind.order() as i32);
+            assert!(
+                (ratio / expected - 1.).abs() < 0.2,
+                "{}: the error was divided by {} instead of {}",
+                kind,
+                ratio,
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn names_round_trip() {
+        for kind in IntegratorKind::ALL {
+            let name = match kind {
+                IntegratorKind::SemiImplicitEuler => "euler",
+                IntegratorKind::Leapfrog => "leapfrog",
+                IntegratorKind::VelocityVerlet => "verlet",
+                IntegratorKind::Rk4 => "rk4",
+            };
+            assert_eq!(name.parse::<IntegratorKind>(), Ok(kind));
+            assert_eq!(kind.to_string().parse::<IntegratorKind>(), Ok(kind));
+            assert_eq!(name.to_uppercase().parse::<IntegratorKind>(), Ok(kind));
+        }
+        assert!("midpoint".parse::<IntegratorKind>().is_err());
+    }
+}
@@ -0,0 +1,264 @@
+use bevy::math::{DQuat, DVec3};
+
+use super::Body;
+
+/// The standard gravitational parameter μ = G(M + m) of a body of mass `mass`
+/// orbiting a primary of mass `primary_mass`.
+pub fn gravitational_parameter(g: f64, primary_mass: f64, mass: f64) -> f64 {
+    g * (primary_mass + mass)
+}
+
+/// The specific orbital energy of a body at `position` moving at `velocity`,
+/// both relative to its primary: negative when bound, positive when escaping.
+pub fn specific_orbital_energy(position: DVec3, velocity: DVec3, mu: f64) -> f64 {
+    velocity.length_squared() / 2. - mu / position.length()
+}
+
+/// The eccentricity vector of a body at `position` moving at `velocity`, both
+/// relative to its primary. It points towards the periapsis and its length is
+/// the eccentricity.
+pub fn eccentricity_vector(position: DVec3, velocity: DVec3, mu: f64) -> DVec3 {
+    ((velocity.length_squared() - mu / position.length()) * position
+        - position.dot(velocity) * velocity)
+        / mu
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+/// The osculating Keplerian elements of a body relative to its primary.
+///
+/// Angles are in radians and measured in the reference plane of the scene,
+/// the XZ plane, with its normal along +Y and the node line origin along +X.
+pub struct OrbitalElements {
+    /// Half the major axis, negative for hyperbolic orbits.
+    pub semi_major_axis: f64,
+    /// How elongated the orbit is: 0 when circular, 1 or more when escaping.
+    pub eccentricity: f64,
+    /// The tilt of the orbital plane relative to the reference plane.
+    pub inclination: f64,
+    /// The angle from the reference direction to the ascending node.
+    pub longitude_of_ascending_node: f64,
+    /// The angle from the ascending node to the periapsis.
+    pub argument_of_periapsis: f64,
+    /// The angle from the periapsis to the body.
+    pub true_anomaly: f64,
+}
+
+impl OrbitalElements {
+    /// Computes the elements of a body at `position` moving at `velocity`,
+    /// both relative to its primary.
+    ///
+    /// The angles that are undefined for circular or equatorial orbits are set
+    /// to 0, the true anomaly then being measured from the node or the reference direction.
+    pub fn from_state_vectors(position: DVec3, velocity: DVec3, mu: f64) -> Self {
+        let position = to_standard(position);
+        let velocity = to_standard(velocity);
+
+        let angular_momentum = position.cross(velocity);
+        let normal = angular_momentum.normalize_or_zero();
+        let node = DVec3::Z
+            .cross(angular_momentum)
+            .try_normalize()
+            .unwrap_or(DVec3::X);
+        let eccentricity_vector = eccentricity_vector(position, velocity, mu);
+        let eccentricity = eccentricity_vector.length();
+        let circular = eccentricity < 1e-9;
+        let periapsis = if circular {
+            node
+        } else {
+            eccentricity_vector / eccentricity
+        };
+        let angle = |from: DVec3, to: DVec3| {
+            normal
+                .dot(from.cross(to))
+                .atan2(from.dot(to))
+                .rem_euclid(std::f64::consts::TAU)
+        };
+
+        Self {
+            semi_major_axis: -mu / (2. * specific_orbital_energy(position, velocity, mu)),
+            eccentricity,
+            inclination: (normal.z).clamp(-1., 1.).acos(),
+            longitude_of_ascending_node: node.y.atan2(node.x).rem_euclid(std::f64::consts::TAU),
+            argument_of_periapsis: if circular { 0. } else { angle(node, periapsis) },
+            true_anomaly: angle(periapsis, position),
+        }
+    }
+
+    /// The elements of an orbit whose periapsis is at `distance` from the
+    /// primary, with the body at the periapsis.
+    pub fn at_periapsis(
+        distance: f64,
+        eccentricity: f64,
+        inclination: f64,
+        longitude_of_ascending_node: f64,
+        argument_of_periapsis: f64,
+    ) -> Self {
+        Self {
+            semi_major_axis: distance / (1. - eccentricity),
+            eccentricity,
+            inclination,
+            longitude_of_ascending_node,
+            argument_of_periapsis,
+            true_anomaly: 0.,
+        }
+    }
+
+    /// Computes the position and velocity, relative to the primary, of a body
+    /// on this orbit. Returns `None` for parabolic or inconsistent elements.
+    pub fn to_state_vectors(self, mu: f64) -> Option<(DVec3, DVec3)> {
+        let semi_latus_rectum = self.semi_major_axis * (1. - self.eccentricity.powi(2));
+        let distance = semi_latus_rectum / (1. + self.eccentricity * self.true_anomaly.cos());
+        if !(semi_latus_rectum > 0. && distance > 0. && distance.is_finite() && mu > 0.) {
+            return None;
+        }
+
+        let (sin, cos) = self.true_anomaly.sin_cos();
+        let position = distance * DVec3::new(cos, sin, 0.);
+        let velocity =
+            (mu / semi_latus_rectum).sqrt() * DVec3::new(-sin, self.eccentricity + cos, 0.);
+        let rotation = DQuat::from_rotation_z(self.longitude_of_ascending_node)
+            * DQuat::from_rotation_x(self.inclination)
+            * DQuat::from_rotation_z(self.argument_of_periapsis);
+        Some((
+            from_standard(rotation * position),
+            from_standard(rotation * velocity),
+        ))
+    }
+
+    /// The orbital period, if the orbit is bound.
+    pub fn period(&self, mu: f64) -> Option<f64> {
+        (self.semi_major_axis > 0. && self.eccentricity < 1.)
+            .then(|| std::f64::consts::TAU * (self.semi_major_axis.powi(3) / mu).sqrt())
+    }
+}
+
+/// Converts a vector from the scene's Y-up frame to the Z-up frame the orbital
+/// elements are usually defined in.
+fn to_standard(v: DVec3) -> DVec3 {
+    DVec3::new(v.x, -v.z, v.y)
+}
+
+/// Converts a vector from the Z-up frame back to the scene's Y-up frame.
+fn from_standard(v: DVec3) -> DVec3 {
+    DVec3::new(v.x, v.z, -v.y)
+}
+
+/// Determines which body each body orbits: the body of smallest Hill sphere,
+/// among the more massive ones, that contains it. The most massive bodies,
+/// outside of any Hill sphere, orbit nothing.
+///
+/// `pinned` forces the parents of some bodies, whatever their Hill spheres.
+pub fn orbit_parents(bodies: &[Body], pinned: &[Option<usize>]) -> Vec<Option<usize>> {
+    let mut by_mass = (0..bodies.len()).collect::<Vec<_>>();
+    by_mass.sort_by(|&a, &b| bodies[b].mass.total_cmp(&bodies[a].mass));
+
+    let mut parents = vec![None; bodies.len()];
+    let mut hill_radii = vec![f64::INFINITY; bodies.len()];
+    for (rank, &body) in by_mass.iter().enumerate() {
+        let position = bodies[body].position;
+        let parent = pinned[body].or_else(|| {
+            by_mass[..rank]
+                .iter()
+                .copied()
+                .filter(|&other| position.distance(bodies[other].position) < hill_radii[other])
+                .min_by(|&a, &b| hill_radii[a].total_cmp(&hill_radii[b]))
+        });
+        if let Some(parent) = parent {
+            hill_radii[body] = position.distance(bodies[parent].position)
+                * (bodies[body].mass / (3. * bodies[parent].mass)).cbrt();
+        }
+        parents[body] = parent;
+    }
+    parents
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f64::consts::TAU;
+
+    use super::*;
+
+    const MU: f64 = 4.;
+
+    /// Whether two angles are the same, modulo a full turn.
+    fn same_angle(a: f64, b: f64) -> bool {
+        let difference = (a - b).rem_euclid(TAU);
+        difference.min(TAU - difference) < 1e-9
+    }
+
+    /// Converts `elements` to state vectors and back, checking nothing changed.
+    fn assert_round_trip(elements: OrbitalElements) {
+        let (position, velocity) = elements.to_state_vectors(MU).unwrap();
+        let result = OrbitalElements::from_state_vectors(position, velocity, MU);
+        assert!(
+            (result.semi_major_axis - elements.semi_major_axis).abs() < 1e-9
+                && (result.eccentricity - elements.eccentricity).abs() < 1e-9
+                && same_angle(result.inclination, elements.inclination)
+                && same_angle(
+                    result.longitude_of_ascending_node,
+                    elements.longitude_of_ascending_node
+                )
+                && same_angle(result.argument_of_periapsis, elements.argument_of_periapsis)
+                && same_angle(result.true_anomaly, elements.true_anomaly),
+            "{:?} became {:?}",
+            elements,
+            result
+        );
+    }
+
+    #[test]
+    fn elliptic_orbits_round_trip() {
+        assert_round_trip(OrbitalElements {
+            semi_major_axis: 10.,
+            eccentricity: 0.6,
+            inclination: 0.,
+            longitude_of_ascending_node: 0.,
+            argument_of_periapsis: 1.,
+            true_anomaly: 2.5,
+        });
+    }
+
+    #[test]
+    fn inclined_orbits_round_trip() {
+        for inclination in [0.3, 1.2, 2.5] {
+            assert_round_trip(OrbitalElements {
+                semi_major_axis: 3.,
+                eccentricity: 0.2,
+                inclination,
+                longitude_of_ascending_node: 4.,
+                argument_of_periapsis: 0.7,
+                true_anomaly: 5.,
+            });
+        }
+    }
+
+    #[test]
+    fn near_circular_orbits_round_trip() {
+        let elements = OrbitalElements {
+            semi_major_axis: 5.,
+            eccentricity: 1e-7,
+            inclination: 0.4,
+            longitude_of_ascending_node: 1.,
+            argument_of_periapsis: 2.,
+            true_anomaly: 3.,
+        };
+        let (position, velocity) = elements.to_state_vectors(MU).unwrap();
+        let result = OrbitalElements::from_state_vectors(position, velocity, MU);
+        // the periapsis is ill-defined, but not the body's angle from the node
+        assert!((result.semi_major_axis - elements.semi_major_axis).abs() < 1e-9);
+        assert!(result.eccentricity < 1e-6);
+        assert!(same_angle(result.inclination, elements.inclination));
+        assert!(same_angle(
+            result.longitude_of_ascending_node,
+            elements.longitude_of_ascending_node
+        ));
+        assert!(same_angle(
+            result.argument_of_periapsis + result.true_anomaly,
+            elements.argument_of_periapsis + elements.true_anomaly
+        ));
+
+        let (new_position, new_velocity) = result.to_state_vectors(MU).unwrap();
+        assert!(new_position.distance(position) < 1e-9);
+        assert!(new_velocity.distance(velocity) < 1e-9);
+    }
+}
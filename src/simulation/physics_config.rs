@@ -0,0 +1,138 @@
+use std::fmt;
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use super::{adaptive::AdaptiveStepping, collisions::CollisionResponse, gravity::ForceSolver};
+
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+/// The unit system masses, distances and times are expressed in.
+pub enum UnitSystem {
+    /// Unitless values with `G = 1`, handy to build scenes by hand.
+    #[default]
+    Toy,
+    /// Meters, kilograms and seconds.
+    Si,
+    /// Astronomical units, solar masses and days.
+    Astronomical,
+}
+
+impl UnitSystem {
+    /// Every available unit system, in the order they are shown in the UI.
+    pub const ALL: [UnitSystem; 3] = [UnitSystem::Toy, UnitSystem::Si, UnitSystem::Astronomical];
+
+    /// The gravitational constant expressed in this unit system.
+    pub fn gravitational_constant(self) -> f64 {
+        match self {
+            UnitSystem::Toy => 1.,
+            UnitSystem::Si => 6.674_3e-11,
+            // Square of the Gaussian gravitational constant.
+            UnitSystem::Astronomical => 2.959_122_082_855_911e-4,
+        }
+    }
+
+    /// The default softening length in this unit system, small next to the
+    /// bodies yet keeping close encounters tractable.
+    pub fn default_softening(self) -> f64 {
+        match self {
+            UnitSystem::Toy => 0.1,
+            // about a sixth of the Earth's radius
+            UnitSystem::Si => 1e6,
+            UnitSystem::Astronomical => 1e-5,
+        }
+    }
+
+    /// The name of the length unit.
+    pub fn length_unit(self) -> &'static str {
+        match self {
+            UnitSystem::Toy => "",
+            UnitSystem::Si => "m",
+            UnitSystem::Astronomical => "AU",
+        }
+    }
+
+    /// The name of the mass unit.
+    pub fn mass_unit(self) -> &'static str {
+        match self {
+            UnitSystem::Toy => "",
+            UnitSystem::Si => "kg",
+            UnitSystem::Astronomical => "M☉",
+        }
+    }
+
+    /// The name of the time unit.
+    pub fn time_unit(self) -> &'static str {
+        match self {
+            UnitSystem::Toy => "",
+            UnitSystem::Si => "s",
+            UnitSystem::Astronomical => "d",
+        }
+    }
+
+    /// The name of the velocity unit.
+    pub fn velocity_unit(self) -> &'static str {
+        match self {
+            UnitSystem::Toy => "",
+            UnitSystem::Si => "m/s",
+            UnitSystem::Astronomical => "AU/d",
+        }
+    }
+}
+
+impl fmt::Display for UnitSystem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            UnitSystem::Toy => "Toy (G = 1)",
+            UnitSystem::Si => "SI (m, kg, s)",
+            UnitSystem::Astronomical => "Astronomical (AU, M☉, days)",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+#[derive(Resource, Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+/// The physical constants of the simulation.
+pub struct PhysicsConfig {
+    /// The gravitational constant.
+    pub g: f64,
+    /// The unit system of the scene.
+    pub unit_system: UnitSystem,
+    /// How colliding bodies are handled.
+    pub collision_response: CollisionResponse,
+    /// The Plummer softening length, smoothing the force at close approach.
+    pub softening: f64,
+    /// How the gravitational forces are evaluated.
+    pub force_solver: ForceSolver,
+    /// The Barnes-Hut opening angle θ.
+    pub opening_angle: f64,
+    /// Whether the forces are computed on several threads.
+    pub parallel: bool,
+    /// How each step is split in sub-steps.
+    pub adaptive_stepping: AdaptiveStepping,
+}
+
+impl PhysicsConfig {
+    /// Switches to another unit system, resetting `g` and the softening length
+    /// to their values in that system.
+    pub fn set_unit_system(&mut self, unit_system: UnitSystem) {
+        self.unit_system = unit_system;
+        self.g = unit_system.gravitational_constant();
+        self.softening = unit_system.default_softening();
+    }
+}
+
+impl Default for PhysicsConfig {
+    fn default() -> Self {
+        Self {
+            g: UnitSystem::Toy.gravitational_constant(),
+            unit_system: UnitSystem::Toy,
+            collision_response: CollisionResponse::default(),
+            softening: UnitSystem::Toy.default_softening(),
+            force_solver: ForceSolver::default(),
+            opening_angle: 0.5,
+            parallel: true,
+            adaptive_stepping: AdaptiveStepping::default(),
+        }
+    }
+}
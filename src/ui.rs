@@ -1,18 +1,39 @@
 use bevy::app::AppExit;
 use bevy::prelude::*;
 use bevy_egui::{egui, EguiContexts, EguiPlugin};
+use serde::{Deserialize, Serialize};
 
-use io::SaveLoadPlugin;
+use io::{LoadMode, LoadScene, SaveLoadPlugin};
 
 use perf_ui::DebugUiPlugin;
 use selected_planet_ui::SelectedPlanetUiPlugin;
 
 use crate::camera::{camera_controller::CameraController, MainCamera};
+use crate::planets::SimulationClock;
+use crate::scene::{presets::Preset, SceneSource};
+use crate::simulation::collisions::CollisionResponse;
+use crate::simulation::gravity::ForceSolver;
+use crate::simulation::integrators::IntegratorKind;
+use crate::simulation::physics_config::{PhysicsConfig, UnitSystem};
 use crate::ui::planet_ui::PlanetUiPlugin;
-mod io;
+use collision_log_ui::CollisionLogUiPlugin;
+use diagnostics_ui::DiagnosticsUiPlugin;
+use edit_history::{edit_history_ui, EditHistory, EditHistoryPlugin};
+use hierarchy_ui::HierarchyUiPlugin;
+use notifications_ui::NotificationsUiPlugin;
+use particles_ui::ParticlesUiPlugin;
+use plot_ui::PlotUiPlugin;
+mod collision_log_ui;
+mod diagnostics_ui;
+pub(crate) mod edit_history;
+mod hierarchy_ui;
+pub(crate) mod io;
+pub(crate) mod notifications_ui;
+mod particles_ui;
 
 mod perf_ui;
 mod planet_ui;
+mod plot_ui;
 pub(crate) mod selected_planet_ui;
 
 #[derive(Default, States, Debug, Hash, Eq, Clone, Copy, PartialEq)]
@@ -23,16 +44,24 @@ pub enum SimulationState {
     Paused,
     Running,
     PickSceneFile,
+    ImportSceneFile,
     SaveSceneFile,
 }
 
-#[derive(Resource)]
+#[derive(Resource, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
 /// The configuration of the application.
 pub struct AppConfig {
     pub draw_velocities: bool,
     pub draw_trajectories: bool,
     pub trajectories_number_iterationss: usize,
+    pub trajectories_relative_to_parents: bool,
+    /// A request to add a planet, not saved with the scene.
+    #[serde(skip)]
     pub add_new_planet: bool,
+    /// The radius in pixels under which bodies are picked as if they were that
+    /// large on screen.
+    pub min_pick_radius: f32,
 }
 
 impl Default for AppConfig {
@@ -42,7 +71,9 @@ impl Default for AppConfig {
             draw_velocities: true,
             draw_trajectories: true,
             trajectories_number_iterationss: 500,
+            trajectories_relative_to_parents: true,
             add_new_planet: false,
+            min_pick_radius: 8.,
         }
     }
 }
@@ -54,22 +85,39 @@ impl Plugin for UIPlugin {
     fn build(&self, app: &mut App) {
         app.init_resource::<AppConfig>()
             .init_state::<SimulationState>()
+            .add_event::<LoadScene>()
+            .add_event::<io::SceneReplaced>()
             .add_plugins(EguiPlugin)
-            .add_plugins((DebugUiPlugin, SelectedPlanetUiPlugin, PlanetUiPlugin))
-            .add_systems(Update, (build_ui, ui_controls));
-
-        #[cfg(not(target_arch = "wasm32"))]
-        app.add_plugins(SaveLoadPlugin);
+            .add_plugins((
+                DebugUiPlugin,
+                SelectedPlanetUiPlugin,
+                PlanetUiPlugin,
+                CollisionLogUiPlugin,
+                DiagnosticsUiPlugin,
+                PlotUiPlugin,
+                HierarchyUiPlugin,
+                ParticlesUiPlugin,
+                EditHistoryPlugin,
+                NotificationsUiPlugin,
+            ))
+            .add_systems(Update, (build_ui, ui_controls))
+            .add_plugins(SaveLoadPlugin);
     }
 }
 
 /// Builds the side panel of the application.
+#[allow(clippy::too_many_arguments)]
 fn build_ui(
     mut contexts: EguiContexts,
     mut app_config: ResMut<AppConfig>,
+    mut integrator: ResMut<IntegratorKind>,
+    mut physics_config: ResMut<PhysicsConfig>,
+    mut clock: ResMut<SimulationClock>,
+    mut history: ResMut<EditHistory>,
     sim_state: Res<State<SimulationState>>,
     mut next_sim_state: ResMut<NextState<SimulationState>>,
     mut app_exit_events: ResMut<Events<bevy::app::AppExit>>,
+    mut load_events: EventWriter<LoadScene>,
     query_cam: Query<&Transform, With<MainCamera>>,
 ) {
     // settings panel
@@ -79,9 +127,14 @@ fn build_ui(
             match *sim_state.get() {
                 SimulationState::Paused => {
                     ui.heading("Paused");
-                    if ui.button("Resume").clicked() {
-                        next_sim_state.set(SimulationState::Running);
-                    }
+                    ui.horizontal(|ui| {
+                        if ui.button("Resume").clicked() {
+                            next_sim_state.set(SimulationState::Running);
+                        }
+                        if ui.button("Step once").clicked() {
+                            clock.step_once = true;
+                        }
+                    });
                 }
                 SimulationState::Running => {
                     ui.heading("Running");
@@ -89,7 +142,29 @@ fn build_ui(
                         next_sim_state.set(SimulationState::Paused);
                     }
                 }
-                SimulationState::PickSceneFile | SimulationState::SaveSceneFile => return,
+                SimulationState::PickSceneFile
+                | SimulationState::ImportSceneFile
+                | SimulationState::SaveSceneFile => return,
+            }
+
+            ui.label(format!(
+                "Simulated time : {:.2}{}",
+                clock.elapsed,
+                physics_config.unit_system.time_unit()
+            ));
+            ui.add(
+                egui::Slider::new(&mut clock.time_warp, SimulationClock::TIME_WARP_RANGE)
+                    .logarithmic(true)
+                    .suffix("×")
+                    .text("Time warp"),
+            );
+            if *sim_state.get() == SimulationState::Running
+                && clock.applied_time_warp < clock.time_warp * 0.99
+            {
+                ui.label(format!(
+                    "Slowed down to {:.1}× by the step budget",
+                    clock.applied_time_warp
+                ));
             }
 
             if ui.button("Add new planet").clicked() {
@@ -97,6 +172,10 @@ fn build_ui(
             };
             ui.checkbox(&mut app_config.draw_velocities, "Draw velocities");
             ui.checkbox(&mut app_config.draw_trajectories, "Draw trajectories");
+            ui.checkbox(
+                &mut app_config.trajectories_relative_to_parents,
+                "Trajectories relative to parents",
+            );
 
             ui.horizontal(|ui| {
                 ui.add(
@@ -105,21 +184,160 @@ fn build_ui(
                 );
                 ui.label("Future trajectories steps");
             });
+            ui.horizontal(|ui| {
+                ui.add(
+                    egui::DragValue::new(&mut app_config.min_pick_radius)
+                        .speed(0.1)
+                        .clamp_range(0.0..=100.0),
+                );
+                ui.label("Minimum pick radius (px)");
+            });
+
+            egui::ComboBox::from_label("Integrator")
+                .selected_text(integrator.to_string())
+                .show_ui(ui, |ui| {
+                    for kind in IntegratorKind::ALL {
+                        ui.selectable_value(&mut *integrator, kind, kind.to_string());
+                    }
+                });
+
+            ui.collapsing("Physics", |ui| {
+                egui::ComboBox::from_label("Units")
+                    .selected_text(physics_config.unit_system.to_string())
+                    .show_ui(ui, |ui| {
+                        for unit_system in UnitSystem::ALL {
+                            if ui
+                                .selectable_label(
+                                    physics_config.unit_system == unit_system,
+                                    unit_system.to_string(),
+                                )
+                                .clicked()
+                            {
+                                physics_config.set_unit_system(unit_system);
+                            }
+                        }
+                    });
+
+                ui.horizontal(|ui| {
+                    let speed = physics_config.g.abs() / 100.;
+                    ui.add(
+                        egui::DragValue::new(&mut physics_config.g)
+                            .speed(speed)
+                            .custom_formatter(|g, _| format!("{:.4e}", g))
+                            .custom_parser(|s| s.parse().ok()),
+                    );
+                    ui.label("Gravitational constant");
+                });
+
+                ui.horizontal(|ui| {
+                    ui.add(
+                        egui::DragValue::new(&mut physics_config.softening)
+                            .speed(0.01)
+                            .clamp_range(0.0..=f64::INFINITY),
+                    );
+                    ui.label("Softening length");
+                });
+
+                egui::ComboBox::from_label("Force solver")
+                    .selected_text(physics_config.force_solver.to_string())
+                    .show_ui(ui, |ui| {
+                        for solver in ForceSolver::ALL {
+                            ui.selectable_value(
+                                &mut physics_config.force_solver,
+                                solver,
+                                solver.to_string(),
+                            );
+                        }
+                    });
+
+                if physics_config.force_solver == ForceSolver::BarnesHut {
+                    ui.add(
+                        egui::Slider::new(&mut physics_config.opening_angle, 0.0..=1.5)
+                            .text("Opening angle θ"),
+                    );
+                }
+
+                ui.checkbox(&mut physics_config.parallel, "Multithreaded forces");
+
+                let adaptive_stepping = &mut physics_config.adaptive_stepping;
+                ui.checkbox(&mut adaptive_stepping.enabled, "Adaptive time step");
+                if adaptive_stepping.enabled {
+                    let min_dt_speed = adaptive_stepping.min_dt / 100.;
+                    let max_dt_speed = adaptive_stepping.max_dt / 100.;
+                    let tolerance_speed = adaptive_stepping.tolerance / 100.;
+                    ui.horizontal(|ui| {
+                        ui.add(
+                            egui::DragValue::new(&mut adaptive_stepping.min_dt)
+                                .speed(min_dt_speed)
+                                .clamp_range(1e-9..=adaptive_stepping.max_dt)
+                                .custom_formatter(|dt, _| format!("{:.2e}", dt))
+                                .custom_parser(|s| s.parse().ok()),
+                        );
+                        ui.add(
+                            egui::DragValue::new(&mut adaptive_stepping.max_dt)
+                                .speed(max_dt_speed)
+                                .clamp_range(adaptive_stepping.min_dt..=f64::INFINITY)
+                                .custom_formatter(|dt, _| format!("{:.2e}", dt))
+                                .custom_parser(|s| s.parse().ok()),
+                        );
+                        ui.label("Min / max sub-step");
+                    });
+                    ui.horizontal(|ui| {
+                        ui.add(
+                            egui::DragValue::new(&mut adaptive_stepping.tolerance)
+                                .speed(tolerance_speed)
+                                .clamp_range(1e-9..=1.0)
+                                .custom_formatter(|tolerance, _| format!("{:.1e}", tolerance))
+                                .custom_parser(|s| s.parse().ok()),
+                        );
+                        ui.label("Error tolerance");
+                    });
+                }
+
+                egui::ComboBox::from_label("Collisions")
+                    .selected_text(physics_config.collision_response.to_string())
+                    .show_ui(ui, |ui| {
+                        for response in CollisionResponse::ALL {
+                            ui.selectable_value(
+                                &mut physics_config.collision_response,
+                                response,
+                                response.to_string(),
+                            );
+                        }
+                    });
+            });
+
+            ui.collapsing("Edit history", |ui| {
+                edit_history_ui(ui, &mut history);
+            });
 
             // ui.collapsing("Debug", |ui| {
             // });
 
-            #[cfg(not(target_arch = "wasm32"))]
             ui.horizontal(|ui| {
                 if cfg!(not(target_arch = "wasm32")) {
                     if ui.button("Open Scene").clicked() {
                         next_sim_state.set(SimulationState::PickSceneFile);
                     };
+                    if ui.button("Import Scene").clicked() {
+                        next_sim_state.set(SimulationState::ImportSceneFile);
+                    };
                     if ui.button("Save Scene").clicked() {
                         next_sim_state.set(SimulationState::SaveSceneFile);
                     };
                 }
-                if ui.button("Quit").clicked() {
+                ui.menu_button("Presets", |ui| {
+                    for preset in Preset::ALL {
+                        if ui.button(preset.to_string()).clicked() {
+                            load_events.send(LoadScene {
+                                source: SceneSource::Preset(preset),
+                                mode: LoadMode::Replace,
+                            });
+                            ui.close_menu();
+                        }
+                    }
+                });
+                if cfg!(not(target_arch = "wasm32")) && ui.button("Quit").clicked() {
                     app_exit_events.send(AppExit);
                 };
             });
@@ -136,20 +354,32 @@ fn build_ui(
                 cam.x, cam.y, cam.z
             ));
             ui.label(format!("{}", CameraController::default()));
+            ui.label("Simulation Controls:\n    Space\t- Pause & resume\n    KeyN\t- Step once while paused\n    Ctrl+Z\t- Undo\n    Ctrl+Shift+Z\t- Redo\n\nSelection Controls:\n    Left click\t- Select a body\n    Shift+Left click\t- Add to or remove from the selection\n    Left drag\t- Select the bodies in a box, Shift to add them\n    Delete\t- Delete the selection");
         });
 }
 
 /// Handles controls for the simulation.
 fn ui_controls(
+    mut contexts: EguiContexts,
     keys: Res<ButtonInput<KeyCode>>,
     sim_state: Res<State<SimulationState>>,
     mut next_sim_state: ResMut<NextState<SimulationState>>,
+    mut clock: ResMut<SimulationClock>,
 ) {
+    // the keys are typed in a text field
+    if contexts.ctx_mut().wants_keyboard_input() {
+        return;
+    }
+    if keys.just_pressed(KeyCode::KeyN) && *sim_state.get() == SimulationState::Paused {
+        clock.step_once = true;
+    }
     if keys.just_pressed(KeyCode::Space) {
         match sim_state.get() {
             SimulationState::Paused => next_sim_state.set(SimulationState::Running),
             SimulationState::Running => next_sim_state.set(SimulationState::Paused),
-            SimulationState::PickSceneFile | SimulationState::SaveSceneFile => (),
+            SimulationState::PickSceneFile
+            | SimulationState::ImportSceneFile
+            | SimulationState::SaveSceneFile => (),
         }
     }
 }
@@ -0,0 +1,74 @@
+use std::collections::VecDeque;
+
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+
+use crate::planets::CollisionEvent;
+use crate::simulation::collisions::CollisionResponse;
+use crate::ui::io::SceneReplaced;
+
+/// How many collisions are kept in the log.
+const MAX_LOGGED_COLLISIONS: usize = 100;
+
+#[derive(Resource, Default)]
+/// The most recent collisions, newest first.
+struct CollisionLog(VecDeque<String>);
+
+/// Plugin responsible for logging collisions and displaying them.
+pub struct CollisionLogUiPlugin;
+
+impl Plugin for CollisionLogUiPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<CollisionLog>().add_systems(
+            Update,
+            (
+                clear_on_scene_replaced,
+                log_collisions,
+                display_collision_log,
+            )
+                .chain(),
+        );
+    }
+}
+
+/// Forgets the collisions of the previous scene.
+fn clear_on_scene_replaced(mut events: EventReader<SceneReplaced>, mut log: ResMut<CollisionLog>) {
+    if events.read().count() > 0 {
+        log.0.clear();
+    }
+}
+
+/// Records the collisions sent by the simulation.
+fn log_collisions(mut events: EventReader<CollisionEvent>, mut log: ResMut<CollisionLog>) {
+    for event in events.read() {
+        let line = match event.response {
+            CollisionResponse::Merge => format!(
+                "{} absorbed {} (relative speed {:.2})",
+                event.first_name, event.second_name, event.relative_speed
+            ),
+            CollisionResponse::Bounce | CollisionResponse::Ignore => format!(
+                "{} bounced off {} (relative speed {:.2})",
+                event.first_name, event.second_name, event.relative_speed
+            ),
+        };
+        info!("{}", line);
+        log.0.push_front(line);
+        log.0.truncate(MAX_LOGGED_COLLISIONS);
+    }
+}
+
+/// Displays the collision log in a floating window.
+fn display_collision_log(mut contexts: EguiContexts, mut log: ResMut<CollisionLog>) {
+    egui::Window::new("Collisions")
+        .default_open(false)
+        .show(contexts.ctx_mut(), |ui| {
+            if ui.button("Clear").clicked() {
+                log.0.clear();
+            }
+            egui::ScrollArea::vertical().show(ui, |ui| {
+                for line in &log.0 {
+                    ui.label(line);
+                }
+            });
+        });
+}
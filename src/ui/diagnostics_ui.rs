@@ -0,0 +1,231 @@
+use std::fs::File;
+use std::io::{LineWriter, Write};
+
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+
+use crate::planets::planet_bundle::{CelestialBodyData, Position};
+use crate::planets::SimulationClock;
+use crate::simulation::{
+    diagnostics::Diagnostics, integrators::IntegratorKind, physics_config::PhysicsConfig,
+    Simulation,
+};
+use crate::ui::io::SceneReplaced;
+use crate::ui::notifications_ui::Notifications;
+
+/// How often, in real seconds, the diagnostics are computed.
+const REFRESH_INTERVAL: f32 = 0.25;
+
+#[derive(Resource)]
+/// Tracks the conserved quantities of the scene and how much they drifted.
+pub struct ConservationMonitor {
+    /// The diagnostics the drifts are measured from.
+    initial: Option<Diagnostics>,
+    /// The diagnostics of the current state.
+    current: Option<Diagnostics>,
+    /// Where the diagnostics are logged.
+    csv_path: String,
+    /// The CSV file being written, if logging.
+    csv: Option<LineWriter<File>>,
+    /// The simulated time of the last logged row, so paused frames are not logged.
+    last_logged: Option<f64>,
+    /// The simulated time `current` was computed at, so it is only computed
+    /// again once the simulation advanced.
+    computed_at: Option<f64>,
+    /// When to compute the diagnostics again.
+    refresh: Timer,
+}
+
+impl Default for ConservationMonitor {
+    fn default() -> Self {
+        Self {
+            initial: None,
+            current: None,
+            csv_path: String::from("diagnostics.csv"),
+            csv: None,
+            last_logged: None,
+            computed_at: None,
+            refresh: Timer::from_seconds(REFRESH_INTERVAL, TimerMode::Repeating),
+        }
+    }
+}
+
+impl ConservationMonitor {
+    /// Computes a drift between the initial and the current diagnostics.
+    pub fn drift(&self, drift: fn(&Diagnostics, &Diagnostics) -> f64) -> Option<f64> {
+        Some(drift(self.current.as_ref()?, self.initial.as_ref()?))
+    }
+}
+
+/// Plugin responsible for monitoring the conserved quantities and displaying them.
+pub struct DiagnosticsUiPlugin;
+
+impl Plugin for DiagnosticsUiPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ConservationMonitor>().add_systems(
+            Update,
+            (
+                reset_on_scene_replaced,
+                update_diagnostics,
+                display_diagnostics,
+            )
+                .chain(),
+        );
+    }
+}
+
+/// Measures the drifts from the loaded scene rather than the previous one.
+fn reset_on_scene_replaced(
+    mut events: EventReader<SceneReplaced>,
+    mut monitor: ResMut<ConservationMonitor>,
+) {
+    if events.read().count() > 0 {
+        monitor.initial = None;
+        monitor.last_logged = None;
+        monitor.computed_at = None;
+    }
+}
+
+/// Computes the diagnostics of the bodies and logs them if requested.
+///
+/// The potential energy costs as much as a simulation step, so they are
+/// computed every `REFRESH_INTERVAL` once the simulation advanced, and right
+/// away when the drifts have no reference yet.
+fn update_diagnostics(
+    query: Query<(&CelestialBodyData, &Position)>,
+    integrator: Res<IntegratorKind>,
+    physics_config: Res<PhysicsConfig>,
+    clock: Res<SimulationClock>,
+    time: Res<Time<Real>>,
+    mut monitor: ResMut<ConservationMonitor>,
+    mut notifications: ResMut<Notifications>,
+) {
+    let due = monitor.refresh.tick(time.delta()).just_finished() || monitor.initial.is_none();
+    if !due || monitor.computed_at == Some(clock.elapsed) {
+        return;
+    }
+    monitor.computed_at = Some(clock.elapsed);
+
+    let mut simulation = Simulation::new(*physics_config, *integrator);
+    simulation.bodies = query
+        .iter()
+        .map(|(bd, position)| bd.to_body(position.0))
+        .collect();
+    if simulation.bodies.is_empty() {
+        monitor.current = None;
+        return;
+    }
+
+    let diagnostics = simulation.diagnostics();
+    monitor.current = Some(diagnostics);
+    if monitor.initial.is_none() {
+        monitor.initial = Some(diagnostics);
+    }
+
+    if monitor.last_logged == Some(clock.elapsed) {
+        return;
+    }
+    if let Some(csv) = monitor.csv.as_mut() {
+        if let Err(e) = writeln!(csv, "{}", diagnostics.csv_row(clock.elapsed)) {
+            error!("Error while writing diagnostics : {}", e);
+            notifications.error(
+                format!("Could not write {}", monitor.csv_path),
+                vec![e.to_string()],
+            );
+            monitor.csv = None;
+        }
+        monitor.last_logged = Some(clock.elapsed);
+    }
+}
+
+/// Displays the conserved quantities and their drifts in a floating window.
+fn display_diagnostics(
+    mut contexts: EguiContexts,
+    mut monitor: ResMut<ConservationMonitor>,
+    mut notifications: ResMut<Notifications>,
+) {
+    egui::Window::new("Diagnostics")
+        .default_open(false)
+        .show(contexts.ctx_mut(), |ui| {
+            if let (Some(current), Some(initial)) = (monitor.current, monitor.initial) {
+                let vector =
+                    |v: bevy::math::DVec3| format!("({:.3e}, {:.3e}, {:.3e})", v.x, v.y, v.z);
+                egui::Grid::new("Diagnostics grid")
+                    .num_columns(3)
+                    .striped(true)
+                    .show(ui, |ui| {
+                        ui.label("");
+                        ui.label("Value");
+                        ui.label("Drift");
+                        ui.end_row();
+
+                        ui.label("Kinetic energy");
+                        ui.label(format!("{:.6e}", current.kinetic_energy));
+                        ui.end_row();
+
+                        ui.label("Potential energy");
+                        ui.label(format!("{:.6e}", current.potential_energy));
+                        ui.end_row();
+
+                        ui.label("Total energy");
+                        ui.label(format!("{:.6e}", current.total_energy()));
+                        ui.label(format!("{:.2e}", current.energy_drift(&initial)));
+                        ui.end_row();
+
+                        ui.label("Momentum");
+                        ui.label(vector(current.momentum));
+                        ui.label(format!("{:.2e}", current.momentum_drift(&initial)));
+                        ui.end_row();
+
+                        ui.label("Angular momentum");
+                        ui.label(vector(current.angular_momentum));
+                        ui.label(format!("{:.2e}", current.angular_momentum_drift(&initial)));
+                        ui.end_row();
+
+                        ui.label("Center of mass");
+                        ui.label(vector(current.center_of_mass));
+                        ui.end_row();
+                    });
+            } else {
+                ui.label("No bodies");
+            }
+
+            if ui.button("Reset drifts").clicked() {
+                monitor.initial = monitor.current;
+            }
+
+            ui.separator();
+            ui.horizontal(|ui| {
+                ui.add_enabled(
+                    monitor.csv.is_none(),
+                    egui::TextEdit::singleline(&mut monitor.csv_path),
+                );
+                if monitor.csv.is_none() {
+                    if ui.button("Start CSV log").clicked() {
+                        let csv = File::create(&monitor.csv_path).and_then(|file| {
+                            let mut csv = LineWriter::new(file);
+                            writeln!(csv, "{}", Diagnostics::CSV_HEADER).map(|_| csv)
+                        });
+                        match csv {
+                            Err(e) => {
+                                error!("Error while creating {} : {}", monitor.csv_path, e);
+                                notifications.error(
+                                    format!("Could not create {}", monitor.csv_path),
+                                    vec![e.to_string()],
+                                );
+                            }
+                            Ok(csv) => {
+                                info!("Logging diagnostics to {}", monitor.csv_path);
+                                monitor.csv = Some(csv);
+                                monitor.last_logged = None;
+                                // log the current state at the next refresh, even if paused
+                                monitor.computed_at = None;
+                            }
+                        }
+                    }
+                } else if ui.button("Stop CSV log").clicked() {
+                    monitor.csv = None;
+                }
+            });
+        });
+}
@@ -0,0 +1,676 @@
+use bevy::math::DVec3;
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+
+use crate::planets::planet_bundle::{spawn_body, CelestialBodyData, OrbitParent, Position};
+
+#[derive(Debug, Clone, PartialEq)]
+/// The state of a body as edited by the user.
+pub struct BodySnapshot {
+    pub data: CelestialBodyData,
+    pub position: DVec3,
+    pub orbit_parent: OrbitParent,
+}
+
+impl BodySnapshot {
+    /// Constructs a new `BodySnapshot`.
+    pub fn new(data: &CelestialBodyData, position: &Position, orbit_parent: &OrbitParent) -> Self {
+        Self {
+            data: data.clone(),
+            position: position.0,
+            orbit_parent: *orbit_parent,
+        }
+    }
+
+    /// The names of the fields which differ between two snapshots.
+    fn changed_fields(&self, other: &Self) -> Vec<&'static str> {
+        let (data, other_data) = (&self.data, &other.data);
+        [
+            ("name", data.name != other_data.name),
+            ("type", data.body_type != other_data.body_type),
+            ("mass", data.mass != other_data.mass),
+            ("radius", data.radius != other_data.radius),
+            ("color", data.color != other_data.color),
+            (
+                "emissive factor",
+                data.emissive_factor != other_data.emissive_factor,
+            ),
+            ("light", data.light_factor != other_data.light_factor),
+            ("softening", data.softening != other_data.softening),
+            ("position", self.position != other.position),
+            ("velocity", data.velocity != other_data.velocity),
+            ("orbit parent", self.orbit_parent != other.orbit_parent),
+        ]
+        .into_iter()
+        .filter_map(|(field, changed)| changed.then_some(field))
+        .collect()
+    }
+
+    /// Applies to a body the fields which differ between `from` and `to`, so
+    /// that undoing an edit leaves what the simulation changed since untouched.
+    fn apply_changes(
+        from: &Self,
+        to: &Self,
+        data: &mut CelestialBodyData,
+        position: &mut Position,
+        orbit_parent: &mut OrbitParent,
+    ) {
+        macro_rules! apply {
+            ($from:expr, $to:expr, $target:expr) => {
+                if $from != $to {
+                    $target = $to.clone();
+                }
+            };
+        }
+        apply!(from.data.name, to.data.name, data.name);
+        apply!(from.data.body_type, to.data.body_type, data.body_type);
+        apply!(from.data.mass, to.data.mass, data.mass);
+        apply!(from.data.radius, to.data.radius, data.radius);
+        apply!(from.data.color, to.data.color, data.color);
+        apply!(
+            from.data.emissive_factor,
+            to.data.emissive_factor,
+            data.emissive_factor
+        );
+        apply!(
+            from.data.light_factor,
+            to.data.light_factor,
+            data.light_factor
+        );
+        apply!(from.data.softening, to.data.softening, data.softening);
+        apply!(from.data.velocity, to.data.velocity, data.velocity);
+        apply!(from.position, to.position, position.0);
+        apply!(from.orbit_parent, to.orbit_parent, *orbit_parent);
+    }
+}
+
+#[derive(Debug, Clone)]
+/// The state of a body before and after a modification.
+struct BodyChange {
+    entity: Entity,
+    before: BodySnapshot,
+    after: BodySnapshot,
+}
+
+#[derive(Debug, Clone)]
+/// An undoable edit of the scene.
+enum Edit {
+    /// Properties of one or several bodies were changed.
+    Modify {
+        label: String,
+        fields: Vec<&'static str>,
+        changes: Vec<BodyChange>,
+    },
+    /// Bodies were added, duplicated or loaded.
+    Spawn {
+        label: String,
+        bodies: Vec<(Entity, BodySnapshot)>,
+    },
+    /// Bodies were deleted.
+    Delete {
+        label: String,
+        bodies: Vec<(Entity, BodySnapshot)>,
+    },
+    /// The bodies of the scene were replaced by those of another one.
+    Replace {
+        label: String,
+        deleted: Vec<(Entity, BodySnapshot)>,
+        spawned: Vec<(Entity, BodySnapshot)>,
+    },
+}
+
+impl Edit {
+    /// The description of the edit shown in the history.
+    fn label(&self) -> &str {
+        match self {
+            Edit::Modify { label, .. }
+            | Edit::Spawn { label, .. }
+            | Edit::Delete { label, .. }
+            | Edit::Replace { label, .. } => label,
+        }
+    }
+
+    /// Every entity the edit refers to, mutably.
+    fn entities_mut(&mut self) -> Vec<&mut Entity> {
+        match self {
+            Edit::Modify { changes, .. } => changes
+                .iter_mut()
+                .flat_map(|change| {
+                    std::iter::once(&mut change.entity)
+                        .chain(change.before.orbit_parent.parent.as_mut())
+                        .chain(change.after.orbit_parent.parent.as_mut())
+                })
+                .collect(),
+            Edit::Spawn { bodies, .. } | Edit::Delete { bodies, .. } => bodies
+                .iter_mut()
+                .flat_map(|(entity, snapshot)| {
+                    std::iter::once(entity).chain(snapshot.orbit_parent.parent.as_mut())
+                })
+                .collect(),
+            Edit::Replace {
+                deleted, spawned, ..
+            } => deleted
+                .iter_mut()
+                .chain(spawned)
+                .flat_map(|(entity, snapshot)| {
+                    std::iter::once(entity).chain(snapshot.orbit_parent.parent.as_mut())
+                })
+                .collect(),
+        }
+    }
+}
+
+#[derive(Resource, Default)]
+/// The undo/redo history of the scene edits.
+pub struct EditHistory {
+    edits: Vec<Edit>,
+    /// How many edits are applied, the following ones were undone.
+    applied: usize,
+    /// How many edits should be applied, one edit being undone or redone per frame.
+    target: Option<usize>,
+    /// Whether the last modification is still going on, e.g. a value being
+    /// dragged, and should absorb the following ones.
+    coalescing: bool,
+}
+
+impl EditHistory {
+    /// Records a new edit, dropping the undone ones.
+    fn push(&mut self, edit: Edit) {
+        self.edits.truncate(self.applied);
+        self.edits.push(edit);
+        self.applied = self.edits.len();
+        self.target = None;
+        self.coalescing = false;
+    }
+
+    /// Records a change of the properties of a body, merged into the previous
+    /// one while the same fields are being edited.
+    ///
+    /// `editing` tells whether the user is still dragging or typing.
+    pub fn modify(
+        &mut self,
+        entity: Entity,
+        before: BodySnapshot,
+        after: BodySnapshot,
+        editing: bool,
+    ) {
+        self.modify_many(vec![(entity, before, after)], editing);
+    }
+
+    /// Records a change of the properties of several bodies as a single edit,
+    /// see [`EditHistory::modify`].
+    pub fn modify_many(
+        &mut self,
+        changes: Vec<(Entity, BodySnapshot, BodySnapshot)>,
+        editing: bool,
+    ) {
+        let mut fields = Vec::new();
+        let changes = changes
+            .into_iter()
+            .filter_map(|(entity, before, after)| {
+                let changed = before.changed_fields(&after);
+                for field in &changed {
+                    if !fields.contains(field) {
+                        fields.push(*field);
+                    }
+                }
+                (!changed.is_empty()).then_some(BodyChange {
+                    entity,
+                    before,
+                    after,
+                })
+            })
+            .collect::<Vec<_>>();
+        if changes.is_empty() {
+            self.coalescing &= editing;
+            return;
+        }
+        let label = match changes.as_slice() {
+            [change] => format!("Change {} of {}", fields.join(", "), change.after.data.name),
+            _ => format!("Change {} of {} bodies", fields.join(", "), changes.len()),
+        };
+
+        if self.coalescing && self.applied == self.edits.len() {
+            if let Some(Edit::Modify {
+                label: last_label,
+                fields: last_fields,
+                changes: last_changes,
+            }) = self.edits.last_mut()
+            {
+                let same_bodies = last_changes.len() == changes.len()
+                    && last_changes
+                        .iter()
+                        .zip(&changes)
+                        .all(|(last, change)| last.entity == change.entity);
+                if same_bodies && *last_fields == fields {
+                    *last_label = label;
+                    for (last, change) in last_changes.iter_mut().zip(changes) {
+                        last.after = change.after;
+                    }
+                    self.coalescing = editing;
+                    return;
+                }
+            }
+        }
+        self.push(Edit::Modify {
+            label,
+            fields,
+            changes,
+        });
+        self.coalescing = editing;
+    }
+
+    /// Records new bodies.
+    pub fn spawn(&mut self, label: String, bodies: Vec<(Entity, BodySnapshot)>) {
+        if !bodies.is_empty() {
+            self.push(Edit::Spawn { label, bodies });
+        }
+    }
+
+    /// Records deleted bodies.
+    pub fn delete(&mut self, label: String, bodies: Vec<(Entity, BodySnapshot)>) {
+        if !bodies.is_empty() {
+            self.push(Edit::Delete { label, bodies });
+        }
+    }
+
+    /// Records the replacement of the bodies of the scene, when a scene is
+    /// opened.
+    pub fn replace(
+        &mut self,
+        label: String,
+        deleted: Vec<(Entity, BodySnapshot)>,
+        spawned: Vec<(Entity, BodySnapshot)>,
+    ) {
+        if !deleted.is_empty() || !spawned.is_empty() {
+            self.push(Edit::Replace {
+                label,
+                deleted,
+                spawned,
+            });
+        }
+    }
+
+    /// Whether there is an edit to undo.
+    pub fn can_undo(&self) -> bool {
+        self.target.unwrap_or(self.applied) > 0
+    }
+
+    /// Whether there is an undone edit to redo.
+    pub fn can_redo(&self) -> bool {
+        self.target.unwrap_or(self.applied) < self.edits.len()
+    }
+
+    /// Requests the last applied edit to be undone.
+    pub fn undo(&mut self) {
+        if self.can_undo() {
+            self.target = Some(self.target.unwrap_or(self.applied) - 1);
+        }
+    }
+
+    /// Requests the last undone edit to be redone.
+    pub fn redo(&mut self) {
+        if self.can_redo() {
+            self.target = Some(self.target.unwrap_or(self.applied) + 1);
+        }
+    }
+
+    /// Replaces despawned entities by the ones respawned in their place, given
+    /// as `(old, new)` pairs, in the history and in the orbit parents of the
+    /// living bodies.
+    fn remap<'a>(
+        &mut self,
+        remapped: &[(Entity, Entity)],
+        orbit_parents: impl Iterator<Item = Mut<'a, OrbitParent>>,
+    ) {
+        let respawned = |entity: Entity| {
+            remapped
+                .iter()
+                .find(|(old, _)| *old == entity)
+                .map(|(_, new)| *new)
+        };
+        for edit in &mut self.edits {
+            for entity in edit.entities_mut() {
+                if let Some(new) = respawned(*entity) {
+                    *entity = new;
+                }
+            }
+        }
+        for mut orbit_parent in orbit_parents {
+            if let Some(new) = orbit_parent.parent.and_then(respawned) {
+                orbit_parent.parent = Some(new);
+            }
+        }
+    }
+}
+
+/// Plugin responsible for undoing and redoing the scene edits.
+pub struct EditHistoryPlugin;
+
+impl Plugin for EditHistoryPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<EditHistory>()
+            .add_systems(Update, (history_controls, apply_edit_history).chain());
+    }
+}
+
+/// Handles Ctrl+Z and Ctrl+Shift+Z, unless a text field has the focus.
+fn history_controls(
+    mut contexts: EguiContexts,
+    keys: Res<ButtonInput<KeyCode>>,
+    mut history: ResMut<EditHistory>,
+) {
+    if !keys.just_pressed(KeyCode::KeyZ) || contexts.ctx_mut().wants_keyboard_input() {
+        return;
+    }
+    let control = keys.any_pressed([
+        KeyCode::ControlLeft,
+        KeyCode::ControlRight,
+        KeyCode::SuperLeft,
+        KeyCode::SuperRight,
+    ]);
+    let shift = keys.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]);
+    match (control, shift) {
+        (true, false) => history.undo(),
+        (true, true) => history.redo(),
+        _ => (),
+    }
+}
+
+/// Undoes or redoes one edit towards the requested point of the history.
+///
+/// A single edit is handled per frame so that the entities respawned by one
+/// edit exist when the next one is applied.
+#[allow(clippy::type_complexity)]
+fn apply_edit_history(
+    mut commands: Commands,
+    mut history: ResMut<EditHistory>,
+    mut query: Query<(
+        &mut CelestialBodyData,
+        &mut Position,
+        &mut OrbitParent,
+        &Handle<StandardMaterial>,
+    )>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+) {
+    let Some(target) = history.target else {
+        return;
+    };
+    if target == history.applied {
+        history.target = None;
+        return;
+    }
+    let undo = target < history.applied;
+    let index = if undo {
+        history.applied - 1
+    } else {
+        history.applied
+    };
+    let edit = history.edits[index].clone();
+    info!("{} : {}", if undo { "Undo" } else { "Redo" }, edit.label());
+
+    match (edit, undo) {
+        (Edit::Modify { changes, .. }, _) => {
+            for change in changes {
+                let (from, to) = if undo {
+                    (&change.after, &change.before)
+                } else {
+                    (&change.before, &change.after)
+                };
+                match query.get_mut(change.entity) {
+                    Ok((mut data, mut position, mut orbit_parent, material)) => {
+                        BodySnapshot::apply_changes(
+                            from,
+                            to,
+                            &mut data,
+                            &mut position,
+                            &mut orbit_parent,
+                        );
+                        if let Some(material) = materials.get_mut(material.id()) {
+                            *material = data.material();
+                        }
+                        commands.entity(change.entity).despawn_descendants();
+                        if let Some(point_light) = data.point_light() {
+                            commands.entity(change.entity).with_children(|p| {
+                                p.spawn(point_light);
+                            });
+                        }
+                    }
+                    Err(_) => info!("The edited body no longer exists"),
+                }
+            }
+        }
+        (Edit::Spawn { bodies, .. }, true) | (Edit::Delete { bodies, .. }, false) => {
+            despawn_bodies(&mut commands, bodies);
+        }
+        (Edit::Spawn { bodies, .. }, false) | (Edit::Delete { bodies, .. }, true) => {
+            respawn_bodies(
+                &mut commands,
+                &mut history,
+                &mut meshes,
+                &mut materials,
+                bodies,
+                query.iter_mut().map(|(_, _, orbit_parent, _)| orbit_parent),
+            );
+        }
+        (
+            Edit::Replace {
+                deleted, spawned, ..
+            },
+            _,
+        ) => {
+            let (removed, restored) = if undo {
+                (spawned, deleted)
+            } else {
+                (deleted, spawned)
+            };
+            despawn_bodies(&mut commands, removed);
+            respawn_bodies(
+                &mut commands,
+                &mut history,
+                &mut meshes,
+                &mut materials,
+                restored,
+                query.iter_mut().map(|(_, _, orbit_parent, _)| orbit_parent),
+            );
+        }
+    }
+
+    history.applied = if undo { index } else { index + 1 };
+}
+
+/// Despawns the bodies of an edit, if they still exist.
+fn despawn_bodies(commands: &mut Commands, bodies: Vec<(Entity, BodySnapshot)>) {
+    for (entity, _) in bodies {
+        if let Some(entity_commands) = commands.get_entity(entity) {
+            entity_commands.despawn_recursive();
+        }
+    }
+}
+
+/// Spawns the bodies of an edit again, the history and the bodies orbiting
+/// them, among `orbit_parents` or the respawned ones, referring to them from then on.
+fn respawn_bodies<'a>(
+    commands: &mut Commands,
+    history: &mut EditHistory,
+    meshes: &mut Assets<Mesh>,
+    materials: &mut Assets<StandardMaterial>,
+    bodies: Vec<(Entity, BodySnapshot)>,
+    orbit_parents: impl Iterator<Item = Mut<'a, OrbitParent>>,
+) {
+    let remapped = bodies
+        .iter()
+        .map(|(entity, snapshot)| {
+            let respawned = spawn_body(
+                commands,
+                meshes,
+                materials,
+                snapshot.data.clone(),
+                snapshot.position,
+                snapshot.orbit_parent,
+            );
+            (*entity, respawned)
+        })
+        .collect::<Vec<_>>();
+    // the respawned bodies do not exist yet for `remap` to update them
+    for ((_, snapshot), &(_, respawned)) in bodies.iter().zip(&remapped) {
+        if let Some(&(_, parent)) = remapped
+            .iter()
+            .find(|(old, _)| snapshot.orbit_parent.parent == Some(*old))
+        {
+            commands.entity(respawned).insert(OrbitParent {
+                parent: Some(parent),
+                ..snapshot.orbit_parent
+            });
+        }
+    }
+    history.remap(&remapped, orbit_parents);
+}
+
+/// Shows the undo and redo buttons and the list of edits, clicking an edit
+/// going back or forth to it.
+pub fn edit_history_ui(ui: &mut egui::Ui, history: &mut EditHistory) {
+    ui.horizontal(|ui| {
+        if ui
+            .add_enabled(history.can_undo(), egui::Button::new("Undo"))
+            .clicked()
+        {
+            history.undo();
+        }
+        if ui
+            .add_enabled(history.can_redo(), egui::Button::new("Redo"))
+            .clicked()
+        {
+            history.redo();
+        }
+    });
+
+    let shown = history.target.unwrap_or(history.applied);
+    let mut clicked = None;
+    egui::ScrollArea::vertical()
+        .max_height(200.)
+        .stick_to_bottom(true)
+        .show(ui, |ui| {
+            if ui.selectable_label(shown == 0, "Initial scene").clicked() {
+                clicked = Some(0);
+            }
+            for (index, edit) in history.edits.iter().enumerate() {
+                let mut text = egui::RichText::new(edit.label());
+                if index >= shown {
+                    text = text.weak();
+                }
+                if ui.selectable_label(shown == index + 1, text).clicked() {
+                    clicked = Some(index + 1);
+                }
+            }
+        });
+    if let Some(clicked) = clicked {
+        history.target = Some(clicked);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::system::RunSystemOnce;
+
+    use super::*;
+    use crate::planets::planet_bundle::CelestialBodyType;
+
+    /// A world where a star is orbited by a planet pinned to it.
+    fn star_and_planet() -> (World, [(Entity, BodySnapshot); 2]) {
+        let mut world = World::new();
+        world.init_resource::<Assets<Mesh>>();
+        world.init_resource::<Assets<StandardMaterial>>();
+        world.init_resource::<EditHistory>();
+
+        let mut spawn = |name: &str, orbit_parent| {
+            let snapshot = BodySnapshot {
+                data: CelestialBodyData::new(
+                    String::from(name),
+                    CelestialBodyType::Planet,
+                    1.,
+                    1.,
+                    DVec3::ZERO,
+                    Color::WHITE,
+                ),
+                position: DVec3::ZERO,
+                orbit_parent,
+            };
+            let spawned = snapshot.clone();
+            let entity = world.run_system_once(
+                move |mut commands: Commands,
+                      mut meshes: ResMut<Assets<Mesh>>,
+                      mut materials: ResMut<Assets<StandardMaterial>>| {
+                    spawn_body(
+                        &mut commands,
+                        &mut meshes,
+                        &mut materials,
+                        spawned.data.clone(),
+                        spawned.position,
+                        spawned.orbit_parent,
+                    )
+                },
+            );
+            (entity, snapshot)
+        };
+        let star = spawn("Star", OrbitParent::default());
+        let planet = spawn(
+            "Planet",
+            OrbitParent {
+                parent: Some(star.0),
+                pinned: true,
+            },
+        );
+        (world, [star, planet])
+    }
+
+    /// Deletes `bodies`, then undoes it.
+    fn delete_and_undo(world: &mut World, bodies: Vec<(Entity, BodySnapshot)>) {
+        for (entity, _) in &bodies {
+            world.despawn(*entity);
+        }
+        let mut history = world.resource_mut::<EditHistory>();
+        history.delete(String::from("Delete"), bodies);
+        history.undo();
+        world.run_system_once(apply_edit_history);
+    }
+
+    /// The body named `name` and the body it orbits.
+    fn orbit_of(world: &mut World, name: &str) -> (Entity, OrbitParent) {
+        world
+            .query::<(Entity, &CelestialBodyData, &OrbitParent)>()
+            .iter(world)
+            .find(|(_, data, _)| data.name == name)
+            .map(|(entity, _, orbit_parent)| (entity, *orbit_parent))
+            .unwrap()
+    }
+
+    #[test]
+    fn respawned_parents_keep_their_children() {
+        let (mut world, [star, (planet, _)]) = star_and_planet();
+        delete_and_undo(&mut world, vec![star.clone()]);
+
+        let (respawned, _) = orbit_of(&mut world, "Star");
+        assert_ne!(respawned, star.0);
+        let orbit_parent = *world.get::<OrbitParent>(planet).unwrap();
+        assert_eq!(
+            orbit_parent,
+            OrbitParent {
+                parent: Some(respawned),
+                pinned: true
+            }
+        );
+    }
+
+    #[test]
+    fn bodies_respawned_together_keep_orbiting_each_other() {
+        let (mut world, bodies) = star_and_planet();
+        delete_and_undo(&mut world, bodies.to_vec());
+
+        let (respawned, _) = orbit_of(&mut world, "Star");
+        let (_, orbit_parent) = orbit_of(&mut world, "Planet");
+        assert_eq!(orbit_parent.parent, Some(respawned));
+        assert!(orbit_parent.pinned);
+    }
+}
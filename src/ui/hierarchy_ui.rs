@@ -0,0 +1,98 @@
+use std::collections::{HashMap, HashSet};
+
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+
+use super::selected_planet_ui::SelectedPlanetMarker;
+use crate::planets::planet_bundle::{CelestialBodyData, OrbitParent};
+
+/// Plugin responsible for displaying which body orbits which.
+pub struct HierarchyUiPlugin;
+
+impl Plugin for HierarchyUiPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, display_hierarchy);
+    }
+}
+
+/// Displays the orbit hierarchy as a tree, clicking a body selects it and
+/// Shift-clicking adds it to the selection.
+fn display_hierarchy(
+    mut contexts: EguiContexts,
+    mut commands: Commands,
+    query: Query<(
+        Entity,
+        &CelestialBodyData,
+        &OrbitParent,
+        Has<SelectedPlanetMarker>,
+    )>,
+) {
+    let mut children = HashMap::<Option<Entity>, Vec<Entity>>::new();
+    for (entity, _, orbit_parent, _) in &query {
+        let parent = orbit_parent.parent.filter(|&parent| query.contains(parent));
+        children.entry(parent).or_default().push(entity);
+    }
+
+    egui::Window::new("Hierarchy")
+        .default_open(false)
+        .show(contexts.ctx_mut(), |ui| {
+            let mut clicked = None;
+            let mut visited = HashSet::new();
+            let roots = children.get(&None).cloned().unwrap_or_default();
+            // bodies pinned to each other in a loop have no root to be reached from
+            let looping = query.iter().map(|(entity, ..)| entity).collect::<Vec<_>>();
+            for entity in roots.into_iter().chain(looping) {
+                body_tree(ui, entity, &query, &children, &mut visited, &mut clicked);
+            }
+
+            if let Some(clicked) = clicked {
+                // Shift toggles the clicked body instead of replacing the selection
+                let shift = ui.input(|i| i.modifiers.shift);
+                for (entity, _, _, selected) in &query {
+                    if entity == clicked && !(shift && selected) {
+                        commands.entity(entity).insert(SelectedPlanetMarker);
+                    } else if selected && (entity == clicked || !shift) {
+                        commands.entity(entity).remove::<SelectedPlanetMarker>();
+                    }
+                }
+            }
+        });
+}
+
+/// Shows `entity` and, indented below it, the bodies orbiting it.
+fn body_tree(
+    ui: &mut egui::Ui,
+    entity: Entity,
+    query: &Query<(
+        Entity,
+        &CelestialBodyData,
+        &OrbitParent,
+        Has<SelectedPlanetMarker>,
+    )>,
+    children: &HashMap<Option<Entity>, Vec<Entity>>,
+    visited: &mut HashSet<Entity>,
+    clicked: &mut Option<Entity>,
+) {
+    if !visited.insert(entity) {
+        return;
+    }
+    let Ok((_, data, orbit_parent, selected)) = query.get(entity) else {
+        return;
+    };
+
+    let label = if orbit_parent.pinned {
+        format!("{} 📌", data.name)
+    } else {
+        data.name.clone()
+    };
+    if ui.selectable_label(selected, label).clicked() {
+        *clicked = Some(entity);
+    }
+    if let Some(orbiting) = children.get(&Some(entity)) {
+        ui.indent(entity, |ui| {
+            for &child in orbiting {
+                body_tree(ui, child, query, children, visited, clicked);
+            }
+        });
+    }
+}
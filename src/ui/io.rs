@@ -1,182 +1,780 @@
+use std::collections::HashMap;
 use std::fs;
+use std::path::{Path, PathBuf};
 
 use crate::{
-    camera::MainCamera,
-    planets::planet_bundle::{CelestialBodyBundle, CelestialBodyData, CelestialBodyType},
+    camera::{camera_controller::CameraController, floating_origin::FloatingOrigin, MainCamera},
+    planets::{
+        particles::ParticleCloud,
+        planet_bundle::{spawn_body, CelestialBodyData, OrbitParent, Position, SpawnOrder},
+        SimulationClock,
+    },
+    scene::{
+        presets::Preset, CameraPose, CelestialBodyRelevantData, ImportOptions, NameCollision,
+        SceneFile, SceneIoError, SceneSource, SimulationSettings, SCENE_VERSION,
+    },
+    simulation::{integrators::IntegratorKind, physics_config::PhysicsConfig},
+    ui::{
+        edit_history::{BodySnapshot, EditHistory},
+        notifications_ui::Notifications,
+    },
 };
 
-use super::SimulationState;
-use bevy::prelude::*;
-use bevy_egui::{
-    egui::{self},
-    EguiContexts,
-};
-use serde::{Deserialize, Serialize};
+use super::{AppConfig, SimulationState};
+use bevy::{app::AppExit, ecs::system::SystemParam, prelude::*};
+use bevy_egui::{egui, EguiContexts};
+#[cfg(not(target_arch = "wasm32"))]
+use egui_file::{DialogType, FileDialog, State as DialogState};
+
+/// Usage of the scene arguments, printed on invalid arguments.
+const USAGE: &str = "Usage: solarust [scene.json] [--preset NAME] [--save-on-exit]";
 
-#[derive(Serialize, Deserialize)]
-struct CelestialBodyRelevantData {
-    body_data: CelestialBodyData,
-    position: Vec3,
+#[derive(Resource, Default, Debug, Clone, PartialEq)]
+/// The scene options given on the command line.
+pub struct SceneArgs {
+    /// The scene opened at startup.
+    pub path: Option<PathBuf>,
+    /// The preset opened at startup instead of `path`.
+    pub preset: Option<Preset>,
+    /// Whether the scene is saved back to `path` when the application exits.
+    pub save_on_exit: bool,
+    /// The scene read from `path` or built from `preset`, until it is opened.
+    pub scene: Option<SceneFile>,
 }
 
-#[derive(Default, Serialize, Deserialize)]
-struct AppData {
-    celestial_bodies: Vec<CelestialBodyRelevantData>,
-    camera_position: Vec3,
+impl SceneArgs {
+    /// Parses the command line arguments (without the program name).
+    pub fn parse(args: &[String]) -> Result<Self, String> {
+        let mut scene_args = Self::default();
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--save-on-exit" => scene_args.save_on_exit = true,
+                "--preset" => {
+                    let preset = args
+                        .next()
+                        .ok_or_else(|| format!("Missing value for {}\n{}", arg, USAGE))?;
+                    scene_args.preset = Some(preset.parse()?);
+                }
+                path if !path.starts_with("--") && scene_args.path.is_none() => {
+                    scene_args.path = Some(PathBuf::from(path))
+                }
+                _ => return Err(format!("Unexpected argument {}\n{}", arg, USAGE)),
+            }
+        }
+        if scene_args.save_on_exit && scene_args.path.is_none() {
+            return Err(format!("--save-on-exit needs a scene file\n{}", USAGE));
+        }
+        // with `--save-on-exit`, the preset is saved to the scene file
+        if scene_args.preset.is_some() && scene_args.path.is_some() && !scene_args.save_on_exit {
+            return Err(format!(
+                "A scene file and a preset cannot both be opened\n{}",
+                USAGE
+            ));
+        }
+        Ok(scene_args)
+    }
+
+    /// Where the scene opened at startup comes from.
+    pub fn source(&self) -> Option<SceneSource> {
+        match (&self.preset, &self.path) {
+            (Some(preset), _) => Some(SceneSource::Preset(*preset)),
+            (None, Some(path)) => Some(SceneSource::File(path.clone())),
+            (None, None) => None,
+        }
+    }
+
+    /// Reads the scene given on the command line, unless it is yet to be
+    /// created by `--save-on-exit`.
+    pub fn read_scene(&mut self) -> Result<(), SceneIoError> {
+        self.scene = match self.source() {
+            Some(SceneSource::File(path)) if self.save_on_exit && !path.exists() => None,
+            Some(source) => Some(source.read()?),
+            None => None,
+        };
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// How a loaded scene is combined with the current one.
+pub enum LoadMode {
+    /// Replaces the bodies, the settings, the camera and the simulated time.
+    Replace,
+    /// Adds the bodies to the current scene, with the `ImportOptions`.
+    Import,
+}
+
+#[derive(Event)]
+/// A request to load a scene.
+pub struct LoadScene {
+    pub source: SceneSource,
+    pub mode: LoadMode,
+}
+
+#[derive(Event)]
+/// Sent when the scene was replaced by a loaded one.
+pub struct SceneReplaced;
+
+#[derive(Event)]
+/// A request to save the scene to a file.
+pub struct SaveScene(pub PathBuf);
+
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Resource, Default)]
+/// The in-app file browser, used instead of native dialogs which are not
+/// available everywhere.
+struct SceneDialog {
+    dialog: Option<FileDialog>,
+    /// The last scene opened or saved, where the browser starts.
+    last_path: Option<PathBuf>,
 }
 
 pub struct SaveLoadPlugin;
 
 impl Plugin for SaveLoadPlugin {
     fn build(&self, app: &mut App) {
+        // the presets need no file system, so scenes can be loaded everywhere
+        app.init_resource::<SceneArgs>()
+            .init_resource::<ImportOptions>()
+            .add_systems(Update, load_scene);
+
         #[cfg(not(target_arch = "wasm32"))]
-        app.add_systems(
-            Update,
-            (
-                save_scene.run_if(in_state(SimulationState::SaveSceneFile)),
-                load_scene.run_if(in_state(SimulationState::PickSceneFile)),
-            ),
-        );
+        app.add_event::<SaveScene>()
+            .init_resource::<SceneDialog>()
+            .add_systems(OnEnter(SimulationState::PickSceneFile), open_scene_dialog)
+            .add_systems(OnEnter(SimulationState::ImportSceneFile), open_scene_dialog)
+            .add_systems(OnEnter(SimulationState::SaveSceneFile), open_scene_dialog)
+            .add_systems(
+                Update,
+                (
+                    (
+                        show_scene_dialog.run_if(
+                            in_state(SimulationState::PickSceneFile)
+                                .or_else(in_state(SimulationState::ImportSceneFile))
+                                .or_else(in_state(SimulationState::SaveSceneFile)),
+                        ),
+                        drop_scene_files,
+                    )
+                        .chain()
+                        .before(load_scene),
+                    save_scene.after(load_scene),
+                ),
+            )
+            .add_systems(Last, save_on_exit);
+    }
+}
+
+#[derive(SystemParam)]
+/// Everything a scene file is made of.
+pub struct SceneParams<'w, 's> {
+    bodies: Query<
+        'w,
+        's,
+        (
+            Entity,
+            &'static Position,
+            &'static CelestialBodyData,
+            &'static OrbitParent,
+            &'static SpawnOrder,
+        ),
+    >,
+    camera:
+        Query<'w, 's, (&'static mut Transform, &'static mut CameraController), With<MainCamera>>,
+    origin: ResMut<'w, FloatingOrigin>,
+    physics_config: ResMut<'w, PhysicsConfig>,
+    integrator: ResMut<'w, IntegratorKind>,
+    clock: ResMut<'w, SimulationClock>,
+    fixed_time: ResMut<'w, Time<Fixed>>,
+    app_config: ResMut<'w, AppConfig>,
+    clouds: Query<'w, 's, Entity, With<ParticleCloud>>,
+}
+
+impl SceneParams<'_, '_> {
+    /// Builds the scene file of the current state of the application.
+    pub fn capture(&self) -> SceneFile {
+        // in the order they were spawned, whatever their archetype, so that
+        // saving a loaded scene keeps its order
+        let mut bodies = self.bodies.iter().collect::<Vec<_>>();
+        bodies.sort_by_key(|(.., spawn_order)| **spawn_order);
+        let indices = bodies
+            .iter()
+            .enumerate()
+            .map(|(index, (entity, ..))| (*entity, index))
+            .collect::<HashMap<_, _>>();
+        let celestial_bodies = bodies
+            .into_iter()
+            .map(
+                |(_, position, body, orbit_parent, _)| CelestialBodyRelevantData {
+                    body_data: body.clone(),
+                    position: position.0,
+                    pinned_parent: orbit_parent
+                        .parent
+                        .filter(|_| orbit_parent.pinned)
+                        .and_then(|parent| indices.get(&parent).copied()),
+                },
+            )
+            .collect();
+        let camera = match self.camera.get_single() {
+            Ok((transform, _)) => CameraPose {
+                position: self.origin.to_simulation(transform.translation),
+                rotation: transform.rotation,
+            },
+            Err(_) => CameraPose::default(),
+        };
+
+        SceneFile {
+            version: SCENE_VERSION,
+            celestial_bodies,
+            camera,
+            physics: *self.physics_config,
+            settings: SimulationSettings {
+                integrator: *self.integrator,
+                dt: self.fixed_time.timestep().as_secs_f64(),
+                time_warp: self.clock.time_warp,
+            },
+            app_config: self.app_config.clone(),
+            elapsed: self.clock.elapsed,
+        }
+    }
+
+    /// The bodies of the scene as they were at the start of the frame.
+    pub fn snapshots(&self) -> Vec<(Entity, BodySnapshot)> {
+        self.bodies
+            .iter()
+            .map(|(entity, position, body, orbit_parent, _)| {
+                (entity, BodySnapshot::new(body, position, orbit_parent))
+            })
+            .collect()
+    }
+
+    /// Replaces the current scene: despawns its bodies, `current`, and its
+    /// particles, applies the settings, the camera pose and the simulated time
+    /// of `scene` and spawns its bodies, returning them as they were spawned.
+    ///
+    /// The bodies are given rather than queried since the ones spawned or
+    /// despawned earlier in the frame are not in the query yet.
+    pub fn restore(
+        &mut self,
+        commands: &mut Commands,
+        meshes: &mut Assets<Mesh>,
+        materials: &mut Assets<StandardMaterial>,
+        scene: SceneFile,
+        current: &[(Entity, BodySnapshot)],
+    ) -> Vec<(Entity, BodySnapshot)> {
+        for entity in current
+            .iter()
+            .map(|(entity, _)| *entity)
+            .chain(&self.clouds)
+        {
+            commands.entity(entity).despawn_recursive();
+        }
+
+        *self.physics_config = scene.physics;
+        *self.integrator = scene.settings.integrator;
+        self.fixed_time.set_timestep_seconds(scene.settings.dt);
+        self.clock.time_warp = scene.settings.time_warp;
+        self.clock.elapsed = scene.elapsed;
+        *self.app_config = AppConfig {
+            add_new_planet: self.app_config.add_new_planet,
+            ..scene.app_config
+        };
+        if let Ok((mut transform, mut controller)) = self.camera.get_single_mut() {
+            // moving the origin to the camera keeps its position exact
+            self.origin.0 = scene.camera.position;
+            transform.translation = Vec3::ZERO;
+            transform.rotation = scene.camera.rotation;
+            // the controller picks its yaw and pitch up from the new rotation
+            controller.initialized = false;
+        }
+
+        spawn_bodies(commands, meshes, materials, scene.celestial_bodies)
+    }
+
+    /// Adds the bodies of `scene` to the current one, whose bodies are
+    /// `current`, leaving the settings, the camera and the simulated time
+    /// untouched.
+    pub fn import(
+        commands: &mut Commands,
+        meshes: &mut Assets<Mesh>,
+        materials: &mut Assets<StandardMaterial>,
+        scene: SceneFile,
+        options: &ImportOptions,
+        current: &[(Entity, BodySnapshot)],
+    ) -> Vec<(Entity, BodySnapshot)> {
+        let existing = current
+            .iter()
+            .map(|(_, snapshot)| snapshot.data.name.clone())
+            .collect();
+        let bodies = scene.imported_bodies(options, &existing);
+        spawn_bodies(commands, meshes, materials, bodies)
     }
 }
 
+/// Spawns the bodies of a scene file, returning them as they were spawned.
+fn spawn_bodies(
+    commands: &mut Commands,
+    meshes: &mut Assets<Mesh>,
+    materials: &mut Assets<StandardMaterial>,
+    bodies: Vec<CelestialBodyRelevantData>,
+) -> Vec<(Entity, BodySnapshot)> {
+    let entities = bodies
+        .iter()
+        .map(|body| {
+            spawn_body(
+                commands,
+                meshes,
+                materials,
+                body.body_data.clone(),
+                body.position,
+                OrbitParent::default(),
+            )
+        })
+        .collect::<Vec<_>>();
+    bodies
+        .into_iter()
+        .zip(&entities)
+        .map(|(body, &entity)| {
+            let parent = body
+                .pinned_parent
+                .and_then(|parent| entities.get(parent).copied());
+            let orbit_parent = OrbitParent {
+                parent,
+                pinned: parent.is_some(),
+            };
+            if orbit_parent.pinned {
+                commands.entity(entity).insert(orbit_parent);
+            }
+            let snapshot = BodySnapshot {
+                data: body.body_data,
+                position: body.position,
+                orbit_parent,
+            };
+            (entity, snapshot)
+        })
+        .collect()
+}
+
+/// Opens the file browser matching the state: picking a scene to open, scenes
+/// to import or a file to save to.
 #[cfg(not(target_arch = "wasm32"))]
-fn save_scene(
+fn open_scene_dialog(
+    sim_state: Res<State<SimulationState>>,
+    mut scene_dialog: ResMut<SceneDialog>,
+) {
+    let path = scene_dialog.last_path.clone();
+    let mut dialog = match sim_state.get() {
+        SimulationState::SaveSceneFile => FileDialog::save_file(path)
+            .default_filename("scene.json")
+            .title("Save current scene"),
+        SimulationState::ImportSceneFile => FileDialog::open_file(path)
+            .multi_select(true)
+            .title("Import scene files"),
+        _ => FileDialog::open_file(path).title("Open scene file"),
+    }
+    .show_files_filter(Box::new(|path: &Path| {
+        path.extension()
+            .is_some_and(|extension| extension == "json")
+    }));
+    dialog.open();
+    scene_dialog.dialog = Some(dialog);
+}
+
+/// Shows the file browser until a file is picked or the browser is closed,
+/// along with the import options when importing.
+#[cfg(not(target_arch = "wasm32"))]
+fn show_scene_dialog(
     mut contexts: EguiContexts,
+    mut scene_dialog: ResMut<SceneDialog>,
+    mut import_options: ResMut<ImportOptions>,
+    sim_state: Res<State<SimulationState>>,
     mut next_sim_state: ResMut<NextState<SimulationState>>,
-    query: Query<(&Transform, &CelestialBodyData), Without<MainCamera>>,
-    query_cam: Query<&Transform, With<MainCamera>>,
+    mut load_events: EventWriter<LoadScene>,
+    mut save_events: EventWriter<SaveScene>,
 ) {
-    use tinyfiledialogs::save_file_dialog;
-
-    egui::Window::new("Save File").show(contexts.ctx_mut(), |ui| {
-        ui.spinner();
+    let Some(dialog) = scene_dialog.dialog.as_mut() else {
+        next_sim_state.set(SimulationState::Paused);
+        return;
+    };
+    let mode = if *sim_state.get() == SimulationState::ImportSceneFile {
+        egui::Window::new("Import options").show(contexts.ctx_mut(), |ui| {
+            import_options_ui(ui, &mut import_options);
+        });
+        LoadMode::Import
+    } else {
+        LoadMode::Replace
+    };
 
-        let path_to_save = match save_file_dialog("Save current scene", "") {
-            Some(path) => {
-                info!("{}", path);
-                path
+    match dialog.show(contexts.ctx_mut()).state() {
+        DialogState::Open => return,
+        DialogState::Selected => match dialog.dialog_type() {
+            DialogType::SaveFile => {
+                if let Some(path) = dialog.path() {
+                    save_events.send(SaveScene(path.to_path_buf()));
+                }
             }
-            None => {
-                ui.label("An error occured");
-                info!("Error while fetching path");
-                return;
+            _ => {
+                let mut paths = dialog.selection();
+                if paths.is_empty() {
+                    paths.extend(dialog.path());
+                }
+                for path in paths {
+                    load_events.send(LoadScene {
+                        source: SceneSource::File(path.to_path_buf()),
+                        mode,
+                    });
+                }
             }
-        };
-        let mut data = AppData {
-            camera_position: query_cam.single().translation,
-            ..Default::default()
-        };
-
-        for (transform, body) in &query {
-            data.celestial_bodies.push(CelestialBodyRelevantData {
-                body_data: body.clone(),
-                position: transform.translation,
-            });
-        }
+        },
+        DialogState::Closed | DialogState::Cancelled => (),
+    }
+    scene_dialog.dialog = None;
+    next_sim_state.set(SimulationState::Paused);
+}
 
-        let serialized_data = match serde_json::to_string(&data) {
-            Ok(data) => data,
-            Err(e) => {
-                info!("Error : {}", e);
-                return;
+/// Edits how the imported bodies are merged into the scene.
+#[cfg(not(target_arch = "wasm32"))]
+fn import_options_ui(ui: &mut egui::Ui, options: &mut ImportOptions) {
+    ui.horizontal(|ui| {
+        ui.add(egui::DragValue::new(&mut options.offset.x));
+        ui.add(egui::DragValue::new(&mut options.offset.y));
+        ui.add(egui::DragValue::new(&mut options.offset.z));
+        ui.label("Position offset");
+    });
+    ui.horizontal(|ui| {
+        ui.add(egui::DragValue::new(&mut options.velocity_offset.x));
+        ui.add(egui::DragValue::new(&mut options.velocity_offset.y));
+        ui.add(egui::DragValue::new(&mut options.velocity_offset.z));
+        ui.label("Velocity offset");
+    });
+    egui::ComboBox::from_label("Existing names")
+        .selected_text(options.name_collision.to_string())
+        .show_ui(ui, |ui| {
+            for name_collision in NameCollision::ALL {
+                ui.selectable_value(
+                    &mut options.name_collision,
+                    name_collision,
+                    name_collision.to_string(),
+                );
             }
-        };
+        });
+}
 
-        let res = fs::write(&path_to_save, serialized_data);
-        match res {
-            Ok(_) => info!("File saved to {}", path_to_save),
-            Err(e) => info!("Error : {}", e),
-        }
+/// Loads the files dropped onto the window: the first one replaces the scene
+/// and the others are imported into it.
+#[cfg(not(target_arch = "wasm32"))]
+fn drop_scene_files(
+    mut drop_events: EventReader<FileDragAndDrop>,
+    mut load_events: EventWriter<LoadScene>,
+) {
+    let dropped = drop_events.read().filter_map(|event| match event {
+        FileDragAndDrop::DroppedFile { path_buf, .. } => Some(path_buf),
+        _ => None,
     });
+    for (index, path) in dropped.enumerate() {
+        load_events.send(LoadScene {
+            source: SceneSource::File(path.clone()),
+            mode: if index == 0 {
+                LoadMode::Replace
+            } else {
+                LoadMode::Import
+            },
+        });
+    }
+}
 
-    next_sim_state.set(SimulationState::Paused);
+/// Writes the current scene to `path`.
+#[cfg(not(target_arch = "wasm32"))]
+fn write_scene(path: &Path, scene: &SceneParams) -> Result<(), SceneIoError> {
+    let serialized_data = scene
+        .capture()
+        .to_json()
+        .map_err(|e| SceneIoError::Serialize(path.to_path_buf(), e))?;
+    fs::write(path, serialized_data).map_err(|e| SceneIoError::Write(path.to_path_buf(), e))
 }
+
 #[cfg(not(target_arch = "wasm32"))]
+fn save_scene(
+    mut save_events: EventReader<SaveScene>,
+    mut scene_dialog: ResMut<SceneDialog>,
+    mut notifications: ResMut<Notifications>,
+    scene: SceneParams,
+) {
+    for SaveScene(path) in save_events.read() {
+        match write_scene(path, &scene) {
+            Ok(_) => {
+                info!("File saved to {}", path.display());
+                notifications.info(format!("Saved {}", path.display()));
+                scene_dialog.last_path = Some(path.clone());
+            }
+            Err(e) => {
+                error!("{}", e);
+                notifications.error(String::from("Could not save the scene"), e.details());
+            }
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
 fn load_scene(
-    mut contexts: EguiContexts,
     mut commands: Commands,
+    mut load_events: EventReader<LoadScene>,
+    mut replaced_events: EventWriter<SceneReplaced>,
+    #[cfg(not(target_arch = "wasm32"))] mut scene_dialog: ResMut<SceneDialog>,
+    import_options: Res<ImportOptions>,
     mut next_sim_state: ResMut<NextState<SimulationState>>,
     mut materials: ResMut<Assets<StandardMaterial>>,
     mut meshes: ResMut<Assets<Mesh>>,
+    mut scene_params: SceneParams,
+    mut history: ResMut<EditHistory>,
+    mut notifications: ResMut<Notifications>,
+    mut args: ResMut<SceneArgs>,
 ) {
-    use tinyfiledialogs::open_file_dialog_multi;
-
-    egui::Window::new("Open File").show(contexts.ctx_mut(), |ui| {
-        ui.spinner();
-        let list_path_to_open = match open_file_dialog_multi("Open scene file", "", None) {
-            Some(path) => {
-                info!("{:?}", path);
-                path
+    // the scene given on the command line was read before starting
+    let startup_scene = args
+        .scene
+        .take()
+        .zip(args.source())
+        .map(|(scene, source)| (source, LoadMode::Replace, Ok(scene)));
+    let requested = load_events
+        .read()
+        .map(|LoadScene { source, mode }| (source.clone(), *mode, source.read()));
+    // the bodies of the scene as each file leaves it, the commands of the
+    // previous ones being applied at the end of the frame
+    let mut current = scene_params.snapshots();
+    for (source, mode, scene) in startup_scene.into_iter().chain(requested) {
+        match scene {
+            Err(e) => {
+                error!("{}", e);
+                let title = match mode {
+                    LoadMode::Replace => "Could not open the scene",
+                    LoadMode::Import => "Could not import the scene",
+                };
+                notifications.error(String::from(title), e.details());
             }
-            None => {
-                ui.label("An error occured");
-                info!("Error while fetching path");
-                return;
+            Ok(scene) => {
+                match mode {
+                    LoadMode::Replace => {
+                        let spawned = scene_params.restore(
+                            &mut commands,
+                            &mut meshes,
+                            &mut materials,
+                            scene,
+                            &current,
+                        );
+                        let deleted = std::mem::replace(&mut current, spawned.clone());
+                        // only the bodies are brought back by undoing it
+                        history.replace(format!("Open {}", source), deleted, spawned);
+                        replaced_events.send(SceneReplaced);
+                        info!("Opened {}", source);
+                        notifications.info(format!("Opened {}", source));
+                    }
+                    LoadMode::Import => {
+                        let spawned = SceneParams::import(
+                            &mut commands,
+                            &mut meshes,
+                            &mut materials,
+                            scene,
+                            &import_options,
+                            &current,
+                        );
+                        current.extend(spawned.iter().cloned());
+                        let message = format!("Imported {} bodies from {}", spawned.len(), source);
+                        info!("{}", message);
+                        notifications.info(message);
+                        history.spawn(format!("Import {}", source), spawned);
+                    }
+                }
+                #[cfg(not(target_arch = "wasm32"))]
+                if let SceneSource::File(path) = source {
+                    scene_dialog.last_path = Some(path);
+                }
+                next_sim_state.set(SimulationState::Paused);
             }
+        }
+    }
+}
+
+/// Saves the scene back to the file given on the command line when the
+/// application exits, if `--save-on-exit` was given.
+#[cfg(not(target_arch = "wasm32"))]
+fn save_on_exit(mut exit_events: EventReader<AppExit>, args: Res<SceneArgs>, scene: SceneParams) {
+    if exit_events.read().count() == 0 || !args.save_on_exit {
+        return;
+    }
+    let Some(path) = &args.path else {
+        return;
+    };
+    match write_scene(path, &scene) {
+        Ok(_) => info!("Scene saved to {} on exit", path.display()),
+        Err(e) => error!("Error while saving {} on exit : {}", path.display(), e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::system::RunSystemOnce;
+    use bevy::math::DVec3;
+
+    use super::*;
+    use crate::scene::tests::sample_scene;
+
+    /// A world with what a scene is restored into.
+    fn scene_world() -> World {
+        let mut world = World::new();
+        world.init_resource::<Assets<Mesh>>();
+        world.init_resource::<Assets<StandardMaterial>>();
+        world.init_resource::<FloatingOrigin>();
+        world.init_resource::<PhysicsConfig>();
+        world.init_resource::<IntegratorKind>();
+        world.init_resource::<SimulationClock>();
+        world.init_resource::<Time<Fixed>>();
+        world.init_resource::<AppConfig>();
+        world.spawn((
+            Transform::default(),
+            CameraController::default(),
+            MainCamera,
+        ));
+        world
+    }
+
+    /// Restores `scene` in `world`, returning the bodies recorded in the history.
+    fn restore(world: &mut World, scene: SceneFile) -> Vec<(Entity, BodySnapshot)> {
+        world.run_system_once(
+            move |mut commands: Commands,
+                  mut meshes: ResMut<Assets<Mesh>>,
+                  mut materials: ResMut<Assets<StandardMaterial>>,
+                  mut scene_params: SceneParams| {
+                let current = scene_params.snapshots();
+                scene_params.restore(
+                    &mut commands,
+                    &mut meshes,
+                    &mut materials,
+                    scene.clone(),
+                    &current,
+                )
+            },
+        )
+    }
+
+    fn capture(world: &mut World) -> SceneFile {
+        world.run_system_once(|scene_params: SceneParams| scene_params.capture())
+    }
+
+    #[test]
+    fn restored_scenes_are_saved_identically() {
+        let scene = sample_scene();
+        let mut world = scene_world();
+        restore(&mut world, scene.clone());
+        assert_eq!(capture(&mut world), scene);
+
+        // saving and reloading again changes nothing
+        let json = capture(&mut world).to_json().unwrap();
+        let mut other_world = scene_world();
+        restore(&mut other_world, SceneFile::from_json(&json).unwrap());
+        assert_eq!(capture(&mut other_world), scene);
+    }
+
+    #[test]
+    fn bodies_are_saved_in_the_order_they_were_opened() {
+        let scene = sample_scene();
+        let mut world = scene_world();
+        let bodies = restore(&mut world, scene.clone());
+        // the freed entity is reused with a new generation by the next scene
+        let (deleted, _) = bodies[1];
+        despawn_with_children_recursive(&mut world, deleted);
+
+        restore(&mut world, scene.clone());
+        let names = |scene: &SceneFile| {
+            scene
+                .celestial_bodies
+                .iter()
+                .map(|body| body.body_data.name.clone())
+                .collect::<Vec<_>>()
         };
+        let saved = capture(&mut world);
+        assert_eq!(names(&saved), names(&scene));
+        assert_eq!(saved, scene);
+    }
 
-        for path in list_path_to_open {
-            match fs::read_to_string(&path) {
-                Err(e) => info!("Error while reading {} : {}", path, e),
-                Ok(data) => {
-                    let app_data = serde_json::from_str::<AppData>(&data);
-                    match app_data {
-                        Err(e) => info!("Error while deserializing data from {} : {}", path, e),
-                        Ok(app_data) => {
-                            for body in app_data.celestial_bodies {
-                                let color = Color::rgb_from_array(body.body_data.color);
-                                let material = materials.add(StandardMaterial {
-                                    base_color: color,
-                                    emissive: color * body.body_data.emissive_factor,
-
-                                    ..Default::default()
-                                });
-
-                                let mesh = meshes.add(Sphere::new(1.0).mesh().ico(5).unwrap());
-
-                                let mut entity_command = commands.spawn((CelestialBodyBundle {
-                                    pbr: PbrBundle {
-                                        mesh,
-                                        material,
-                                        transform: Transform {
-                                            translation: body.position,
-                                            scale: Vec3::ONE * body.body_data.radius,
-                                            ..default()
-                                        },
-                                        ..Default::default()
-                                    },
-
-                                    body_data: CelestialBodyData::new(
-                                        body.body_data.name,
-                                        body.body_data.body_type.clone(),
-                                        body.body_data.mass,
-                                        body.body_data.radius,
-                                        body.body_data.velocity,
-                                        color,
-                                    ),
-                                },));
-                                if body.body_data.body_type == CelestialBodyType::Star {
-                                    entity_command.with_children(|p| {
-                                        p.spawn(PointLightBundle {
-                                            point_light: PointLight {
-                                                color: Color::WHITE,
-                                                intensity: body.body_data.light_factor,
-                                                range: 1000.0,
-                                                radius: body.body_data.radius,
-                                                ..default()
-                                            },
-                                            ..default()
-                                        });
-                                    });
-                                }
-                            }
-                        }
-                    }
+    #[test]
+    fn imported_bodies_are_added_and_replacing_removes_them() {
+        let scene = sample_scene();
+        let mut world = scene_world();
+        restore(&mut world, scene.clone());
+
+        let imported = scene.clone();
+        let options = ImportOptions {
+            offset: DVec3::new(1000., 0., 0.),
+            ..Default::default()
+        };
+        // imported twice in the same frame, like several files dropped at once
+        let spawned = world.run_system_once(
+            move |mut commands: Commands,
+                  mut meshes: ResMut<Assets<Mesh>>,
+                  mut materials: ResMut<Assets<StandardMaterial>>,
+                  scene_params: SceneParams| {
+                let mut current = scene_params.snapshots();
+                let mut spawned = Vec::new();
+                for _ in 0..2 {
+                    let bodies = SceneParams::import(
+                        &mut commands,
+                        &mut meshes,
+                        &mut materials,
+                        imported.clone(),
+                        &options,
+                        &current,
+                    );
+                    current.extend(bodies.iter().cloned());
+                    spawned.push(bodies);
                 }
+                spawned
+            },
+        );
+        let count = scene.celestial_bodies.len();
+        let captured = capture(&mut world);
+        assert_eq!(captured.celestial_bodies.len(), 3 * count);
+        for (copy, bodies) in spawned.iter().enumerate() {
+            assert_eq!(bodies.len(), count);
+            for ((_, snapshot), body) in bodies.iter().zip(&scene.celestial_bodies) {
+                assert_eq!(
+                    snapshot.data.name,
+                    format!("{} ({})", body.body_data.name, copy + 2)
+                );
             }
         }
-    });
+        // the settings and the camera are those of the current scene
+        assert_eq!(captured.camera, scene.camera);
+        assert_eq!(captured.elapsed, scene.elapsed);
 
-    next_sim_state.set(SimulationState::Paused);
+        restore(&mut world, scene.clone());
+        assert_eq!(capture(&mut world), scene);
+    }
+
+    #[test]
+    fn restored_bodies_keep_every_field() {
+        let scene = sample_scene();
+        let mut world = scene_world();
+        let spawned = restore(&mut world, scene.clone());
+
+        for ((entity, snapshot), body) in spawned.iter().zip(&scene.celestial_bodies) {
+            assert_eq!(snapshot.data, body.body_data);
+            assert_eq!(
+                world.get::<CelestialBodyData>(*entity),
+                Some(&body.body_data)
+            );
+            assert_eq!(world.get::<Position>(*entity).unwrap().0, body.position);
+            assert_eq!(
+                world.get::<OrbitParent>(*entity),
+                Some(&snapshot.orbit_parent)
+            );
+        }
+        assert_eq!(
+            spawned[1].1.orbit_parent,
+            OrbitParent {
+                parent: Some(spawned[0].0),
+                pinned: true,
+            }
+        );
+    }
 }
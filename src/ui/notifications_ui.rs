@@ -0,0 +1,116 @@
+use std::collections::VecDeque;
+
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+
+/// How many notifications are shown at most.
+const MAX_NOTIFICATIONS: usize = 8;
+
+/// How long, in seconds, information notifications are shown.
+const INFO_DURATION: f64 = 5.;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// How important a notification is.
+pub enum NotificationLevel {
+    /// Shown for a few seconds.
+    Info,
+    /// Shown until it is dismissed.
+    Error,
+}
+
+#[derive(Debug, Clone)]
+/// A message shown in the corner of the window.
+pub struct Notification {
+    pub level: NotificationLevel,
+    pub title: String,
+    /// The lines shown under the title.
+    pub details: Vec<String>,
+    /// When the notification was sent, in real seconds since startup.
+    sent_at: f64,
+}
+
+#[derive(Resource, Default)]
+/// The notifications being shown, newest first.
+pub struct Notifications {
+    notifications: VecDeque<Notification>,
+    /// Real seconds since startup, updated every frame.
+    now: f64,
+}
+
+impl Notifications {
+    /// Shows a notification.
+    pub fn push(&mut self, level: NotificationLevel, title: String, details: Vec<String>) {
+        self.notifications.push_front(Notification {
+            level,
+            title,
+            details,
+            sent_at: self.now,
+        });
+        self.notifications.truncate(MAX_NOTIFICATIONS);
+    }
+
+    /// Shows a notification which disappears after a few seconds.
+    pub fn info(&mut self, title: String) {
+        self.push(NotificationLevel::Info, title, Vec::new());
+    }
+
+    /// Shows an error until it is dismissed.
+    pub fn error(&mut self, title: String, details: Vec<String>) {
+        self.push(NotificationLevel::Error, title, details);
+    }
+}
+
+/// Plugin responsible for displaying the notifications.
+pub struct NotificationsUiPlugin;
+
+impl Plugin for NotificationsUiPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Notifications>()
+            .add_systems(Update, display_notifications);
+    }
+}
+
+/// Displays the notifications in the bottom right corner of the window.
+fn display_notifications(
+    mut contexts: EguiContexts,
+    mut notifications: ResMut<Notifications>,
+    time: Res<Time<Real>>,
+) {
+    let now = time.elapsed_seconds_f64();
+    notifications.now = now;
+    notifications.notifications.retain(|notification| {
+        notification.level == NotificationLevel::Error || now - notification.sent_at < INFO_DURATION
+    });
+    if notifications.notifications.is_empty() {
+        return;
+    }
+
+    let mut dismissed = None;
+    egui::Area::new(egui::Id::new("notifications"))
+        .anchor(egui::Align2::RIGHT_BOTTOM, egui::vec2(-10., -10.))
+        .show(contexts.ctx_mut(), |ui| {
+            for (index, notification) in notifications.notifications.iter().enumerate() {
+                egui::Frame::popup(ui.style()).show(ui, |ui| {
+                    ui.set_max_width(400.);
+                    ui.horizontal(|ui| {
+                        let title = egui::RichText::new(&notification.title).strong();
+                        match notification.level {
+                            NotificationLevel::Info => ui.label(title),
+                            NotificationLevel::Error => {
+                                ui.label(title.color(ui.visuals().error_fg_color))
+                            }
+                        };
+                        if ui.small_button("✖").clicked() {
+                            dismissed = Some(index);
+                        }
+                    });
+                    for line in &notification.details {
+                        ui.label(line);
+                    }
+                });
+            }
+        });
+    if let Some(index) = dismissed {
+        notifications.notifications.remove(index);
+    }
+}
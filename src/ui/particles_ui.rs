@@ -0,0 +1,154 @@
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+
+use super::selected_planet_ui::SelectedPlanetMarker;
+use crate::planets::particles::{CloudLayout, CloudShape, ParticleCloud, ParticleCloudBundle};
+use crate::planets::planet_bundle::{CelestialBodyData, Position};
+use crate::simulation::{orbits::gravitational_parameter, physics_config::PhysicsConfig};
+
+#[derive(Resource)]
+/// The settings of the next particle cloud, lengths being in radii of the
+/// body it is spawned around.
+struct ParticleSpawner {
+    layout: CloudLayout,
+    color: [f32; 3],
+    size: f32,
+}
+
+impl Default for ParticleSpawner {
+    fn default() -> Self {
+        Self {
+            layout: CloudLayout {
+                shape: CloudShape::default(),
+                count: 2000,
+                inner_radius: 1.5,
+                outer_radius: 2.5,
+                thickness: 0.1,
+            },
+            color: [0.8, 0.75, 0.6],
+            size: 0.02,
+        }
+    }
+}
+
+/// Plugin responsible for spawning and clearing particle clouds.
+pub struct ParticlesUiPlugin;
+
+impl Plugin for ParticlesUiPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ParticleSpawner>()
+            .add_systems(Update, display_particles_window);
+    }
+}
+
+/// Displays the particle spawner, spawning clouds around the selected body.
+fn display_particles_window(
+    mut contexts: EguiContexts,
+    mut commands: Commands,
+    mut spawner: ResMut<ParticleSpawner>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    query_selected: Query<(&CelestialBodyData, &Position), With<SelectedPlanetMarker>>,
+    query_clouds: Query<(Entity, &ParticleCloud)>,
+    physics_config: Res<PhysicsConfig>,
+) {
+    egui::Window::new("Particles")
+        .default_open(false)
+        .show(contexts.ctx_mut(), |ui| {
+            let particles = query_clouds
+                .iter()
+                .map(|(_, cloud)| cloud.positions.len())
+                .sum::<usize>();
+            ui.horizontal(|ui| {
+                ui.label(format!(
+                    "{} particles in {} clouds",
+                    particles,
+                    query_clouds.iter().count()
+                ));
+                if ui.button("Clear").clicked() {
+                    for (entity, _) in &query_clouds {
+                        commands.entity(entity).despawn_recursive();
+                    }
+                }
+            });
+            ui.separator();
+
+            let layout = &mut spawner.layout;
+            egui::ComboBox::from_label("Shape")
+                .selected_text(layout.shape.to_string())
+                .show_ui(ui, |ui| {
+                    for shape in CloudShape::ALL {
+                        ui.selectable_value(&mut layout.shape, shape, shape.to_string());
+                    }
+                });
+            ui.horizontal(|ui| {
+                ui.add(
+                    egui::DragValue::new(&mut layout.count)
+                        .speed(100)
+                        .clamp_range(1..=200_000),
+                );
+                ui.label("Particles");
+            });
+            ui.horizontal(|ui| {
+                ui.add(
+                    egui::DragValue::new(&mut layout.inner_radius)
+                        .speed(0.01)
+                        .clamp_range(0.0..=f64::INFINITY),
+                );
+                ui.add(
+                    egui::DragValue::new(&mut layout.outer_radius)
+                        .speed(0.01)
+                        .clamp_range(layout.inner_radius..=f64::INFINITY),
+                );
+                ui.label("Inner / outer radius (body radii)");
+            });
+            if layout.shape == CloudShape::Disk {
+                ui.horizontal(|ui| {
+                    ui.add(
+                        egui::DragValue::new(&mut layout.thickness)
+                            .speed(0.01)
+                            .clamp_range(0.0..=f64::INFINITY),
+                    );
+                    ui.label("Thickness (body radii)");
+                });
+            }
+            ui.horizontal(|ui| {
+                ui.color_edit_button_rgb(&mut spawner.color);
+                ui.add(
+                    egui::DragValue::new(&mut spawner.size)
+                        .speed(0.001)
+                        .clamp_range(0.0..=1.0),
+                );
+                ui.label("Color & size (body radii)");
+            });
+
+            let Ok((body_data, position)) = query_selected.get_single() else {
+                ui.label("Select a body to spawn particles around it");
+                return;
+            };
+            if ui
+                .button(format!("Spawn around {}", body_data.name))
+                .clicked()
+            {
+                let radius = body_data.radius;
+                let layout = CloudLayout {
+                    inner_radius: spawner.layout.inner_radius * radius,
+                    outer_radius: spawner.layout.outer_radius * radius,
+                    thickness: spawner.layout.thickness * radius,
+                    ..spawner.layout
+                };
+                let cloud = ParticleCloud::around(
+                    &layout,
+                    position.0,
+                    body_data.velocity,
+                    gravitational_parameter(physics_config.g, body_data.mass, 0.),
+                    spawner.color,
+                    spawner.size * radius as f32,
+                );
+                info!(
+                    "Spawned {} particles around {}",
+                    layout.count, body_data.name
+                );
+                commands.spawn(ParticleCloudBundle::new(cloud, &mut meshes));
+            }
+        });
+}
@@ -1,8 +1,13 @@
 use bevy::diagnostic::{FrameTimeDiagnosticsPlugin, SystemInformationDiagnosticsPlugin};
+use bevy::ecs::system::{lifetimeless::SRes, SystemParam};
 use bevy::prelude::*;
 use bevy_inspector_egui::quick::WorldInspectorPlugin;
 use iyes_perf_ui::prelude::*;
 
+use super::diagnostics_ui::ConservationMonitor;
+use crate::planets::SubStepStats;
+use crate::simulation::diagnostics::Diagnostics;
+
 /// Plugin that adds the performance UI.
 pub struct DebugUiPlugin;
 
@@ -14,6 +19,10 @@ impl Plugin for DebugUiPlugin {
             FrameTimeDiagnosticsPlugin,
             SystemInformationDiagnosticsPlugin,
         ))
+            .add_perf_ui_entry_type::<PerfUiEntrySubSteps>()
+            .add_perf_ui_entry_type::<PerfUiEntryEnergyDrift>()
+            .add_perf_ui_entry_type::<PerfUiEntryMomentumDrift>()
+            .add_perf_ui_entry_type::<PerfUiEntryAngularMomentumDrift>()
             .add_systems(Startup, perf_ui);
     }
 }
@@ -28,5 +37,106 @@ fn perf_ui(mut commands: Commands) {
         PerfUiEntryFrameTime::default(),
         PerfUiEntryCpuUsage::default(),
         PerfUiEntryMemUsage::default(),
+        PerfUiEntrySubSteps::default(),
+        PerfUiEntryEnergyDrift::default(),
+        PerfUiEntryMomentumDrift::default(),
+        PerfUiEntryAngularMomentumDrift::default(),
     ));
 }
+
+#[derive(Component)]
+/// Perf UI entry showing how many sub-steps the last physics tick took.
+pub struct PerfUiEntrySubSteps {
+    sort_key: i32,
+}
+
+impl Default for PerfUiEntrySubSteps {
+    fn default() -> Self {
+        Self {
+            sort_key: iyes_perf_ui::utils::next_sort_key(),
+        }
+    }
+}
+
+impl PerfUiEntry for PerfUiEntrySubSteps {
+    type Value = usize;
+    type SystemParam = SRes<SubStepStats>;
+
+    fn label(&self) -> &str {
+        "Physics Sub-steps"
+    }
+
+    fn sort_key(&self) -> i32 {
+        self.sort_key
+    }
+
+    fn update_value(
+        &self,
+        stats: &mut <Self::SystemParam as SystemParam>::Item<'_, '_>,
+    ) -> Option<Self::Value> {
+        Some(stats.substeps)
+    }
+}
+
+/// Declares a perf UI entry showing the drift of a conserved quantity since
+/// the `ConservationMonitor` reference.
+macro_rules! drift_entry {
+    ($(#[$doc:meta])* $name:ident, $label:literal, $drift:path) => {
+        #[derive(Component)]
+        $(#[$doc])*
+        pub struct $name {
+            sort_key: i32,
+        }
+
+        impl Default for $name {
+            fn default() -> Self {
+                Self {
+                    sort_key: iyes_perf_ui::utils::next_sort_key(),
+                }
+            }
+        }
+
+        impl PerfUiEntry for $name {
+            type Value = f64;
+            type SystemParam = SRes<ConservationMonitor>;
+
+            fn label(&self) -> &str {
+                $label
+            }
+
+            fn sort_key(&self) -> i32 {
+                self.sort_key
+            }
+
+            fn update_value(
+                &self,
+                monitor: &mut <Self::SystemParam as SystemParam>::Item<'_, '_>,
+            ) -> Option<Self::Value> {
+                monitor.drift($drift)
+            }
+
+            fn format_value(&self, value: &Self::Value) -> String {
+                format!("{:.2e}", value)
+            }
+        }
+    };
+}
+
+drift_entry!(
+    /// Perf UI entry showing the relative drift of the total energy.
+    PerfUiEntryEnergyDrift,
+    "Energy Drift",
+    Diagnostics::energy_drift
+);
+drift_entry!(
+    /// Perf UI entry showing the relative drift of the total momentum.
+    PerfUiEntryMomentumDrift,
+    "Momentum Drift",
+    Diagnostics::momentum_drift
+);
+drift_entry!(
+    /// Perf UI entry showing the relative drift of the total angular momentum.
+    PerfUiEntryAngularMomentumDrift,
+    "Angular Momentum Drift",
+    Diagnostics::angular_momentum_drift
+);
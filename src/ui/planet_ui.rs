@@ -1,5 +1,11 @@
-use crate::planets::planet_bundle::CelestialBodyData;
+use crate::camera::floating_origin::FloatingOrigin;
+use crate::planets::planet_bundle::{CelestialBodyData, CelestialBodyType, OrbitParent, Position};
+use crate::simulation::{
+    collisions::retain_survivors, integrators::IntegratorKind, physics_config::PhysicsConfig,
+    Simulation,
+};
 use crate::ui::AppConfig;
+use bevy::math::DVec3;
 use bevy::prelude::*;
 
 pub struct PlanetUiPlugin;
@@ -7,10 +13,17 @@ pub struct PlanetUiPlugin;
 impl Plugin for PlanetUiPlugin {
     fn build(&self, app: &mut App) {
         app.add_systems(Update, draw_velocity_vectors.run_if(run_if_draw_velocities))
-            .add_systems(Update, draw_trajectories.run_if(run_if_draw_trajectories));
+            .add_systems(Update, draw_trajectories.run_if(run_if_draw_trajectories))
+            .add_systems(Update, (draw_comet_tails, draw_accretion_disks));
     }
 }
 
+/// The length of a comet's tail, in comet radii.
+const COMET_TAIL_LENGTH: f32 = 40.;
+
+/// The number of rings drawn around a black hole.
+const ACCRETION_RINGS: usize = 8;
+
 /// Returns true if the app is configured to draw velocities.
 fn run_if_draw_velocities(app_config: Res<AppConfig>) -> bool {
     app_config.draw_velocities
@@ -27,55 +40,165 @@ fn draw_velocity_vectors(
 
         gizmos.arrow(
             body_position,
-            body_position + 2. * body_velocity / body_data.radius,
+            body_position + (2. * body_velocity / body_data.radius).as_vec3(),
             Color::YELLOW,
         );
     }
 }
 
+/// Draws the tails of the comets, pointing away from the nearest star or
+/// trailing behind the comet when there is none.
+fn draw_comet_tails(mut gizmos: Gizmos, query: Query<(&CelestialBodyData, &Transform)>) {
+    let stars = query
+        .iter()
+        .filter(|(bd, _)| bd.body_type == CelestialBodyType::Star)
+        .map(|(_, transform)| transform.translation)
+        .collect::<Vec<_>>();
+
+    for (body_data, transform) in &query {
+        if body_data.body_type != CelestialBodyType::Comet {
+            continue;
+        }
+        let position = transform.translation;
+        let direction = stars
+            .iter()
+            .min_by(|a, b| {
+                a.distance_squared(position)
+                    .total_cmp(&b.distance_squared(position))
+            })
+            .map_or(-body_data.velocity.as_vec3(), |&star| position - star)
+            .normalize_or_zero();
+        if direction == Vec3::ZERO {
+            continue;
+        }
+
+        let radius = body_data.radius as f32;
+        let color = Color::rgb_from_array(body_data.color);
+        let (side, up) = direction.any_orthonormal_pair();
+        // a narrow cone of lines fading away from the nucleus
+        for i in 0..8 {
+            let angle = i as f32 * std::f32::consts::TAU / 8.;
+            let spread = (side * angle.cos() + up * angle.sin()) * 0.1;
+            gizmos.line_gradient(
+                position + spread * radius * 5.,
+                position + (direction + spread) * radius * COMET_TAIL_LENGTH,
+                color,
+                color.with_a(0.),
+            );
+        }
+        gizmos.line_gradient(
+            position,
+            position + direction * radius * COMET_TAIL_LENGTH * 1.2,
+            color,
+            color.with_a(0.),
+        );
+    }
+}
+
+/// Draws the glowing accretion disks around the black holes, in their
+/// equatorial plane.
+fn draw_accretion_disks(mut gizmos: Gizmos, query: Query<(&CelestialBodyData, &Transform)>) {
+    for (body_data, transform) in &query {
+        if body_data.body_type != CelestialBodyType::BlackHole {
+            continue;
+        }
+        let radius = body_data.radius as f32;
+        let color = Color::rgb_from_array(body_data.color);
+        for ring in 0..ACCRETION_RINGS {
+            let t = ring as f32 / ACCRETION_RINGS as f32;
+            gizmos.circle(
+                transform.translation,
+                Direction3d::Y,
+                radius * (1.5 + 2. * t),
+                color.with_a(1. - t),
+            );
+        }
+    }
+}
+
 /// Returns true if the app is configured to draw trajectories.
 fn run_if_draw_trajectories(app_config: Res<AppConfig>) -> bool {
     app_config.draw_trajectories
 }
 
-/// Draws trajectories for all bodies by simulating their future positions over time.
+/// Draws trajectories for all bodies by simulating their future positions over time
+/// with the same integrator and time step as the simulation itself.
+///
+/// When configured, each trajectory is drawn relative to the body's parent, as if
+/// the parent stayed where it is now, so that moons draw their orbit rather than
+/// loops along their planet's path.
 fn draw_trajectories(
     mut gizmos: Gizmos,
-    query: Query<(&CelestialBodyData, &Transform), With<CelestialBodyData>>,
+    query: Query<(Entity, &CelestialBodyData, &Position, &OrbitParent)>,
     app_config: Res<AppConfig>,
+    integrator: Res<IntegratorKind>,
+    physics_config: Res<PhysicsConfig>,
+    fixed_time: Res<Time<Fixed>>,
+    origin: Res<FloatingOrigin>,
 ) {
-    let g = 1.;
-    let delta_seconds = 0.01;
-    let mut bodies_and_positions = query
+    let delta_seconds = fixed_time.timestep().as_secs_f64();
+
+    let entities = query.iter().map(|(entity, ..)| entity).collect::<Vec<_>>();
+    let colors = query
+        .iter()
+        .map(|(_, bd, _, _)| Color::rgb_from_array(bd.color))
+        .collect::<Vec<_>>();
+    let parents = query
+        .iter()
+        .map(|(_, _, _, orbit_parent)| {
+            orbit_parent
+                .parent
+                .filter(|_| app_config.trajectories_relative_to_parents)
+                .and_then(|parent| entities.iter().position(|&e| e == parent))
+        })
+        .collect::<Vec<_>>();
+    let initial_positions = query
         .iter()
-        .map(|(bd, tfm)| (bd, tfm.translation, bd.velocity))
+        .map(|(_, _, position, _)| position.0)
         .collect::<Vec<_>>();
+    let mut simulation = Simulation::new(*physics_config, *integrator);
+    simulation.bodies = query
+        .iter()
+        .map(|(_, bd, position, _)| bd.to_body(position.0))
+        .collect();
 
+    // where to draw a body given the positions of every body, by index in the query
+    let displayed = |positions: &[Option<DVec3>], body: usize| -> Option<DVec3> {
+        let position = positions[body]?;
+        match parents[body].and_then(|parent| Some((parent, positions[parent]?))) {
+            Some((parent, parent_position)) => {
+                Some(initial_positions[parent] + position - parent_position)
+            }
+            None => Some(position),
+        }
+    };
+
+    // the index in the query of each simulated body
+    let mut survivors = (0..entities.len()).collect::<Vec<_>>();
+    let mut old_positions = initial_positions
+        .iter()
+        .copied()
+        .map(Some)
+        .collect::<Vec<_>>();
     for _ in 0..app_config.trajectories_number_iterationss {
-        let old_bodies_and_positions = bodies_and_positions.clone();
-
-        for i in 0..bodies_and_positions.len() {
-            let mut total_velocity_to_add = Vec3::ZERO;
-            for j in 0..bodies_and_positions.len() {
-                if i == j {
-                    continue;
-                }
-                total_velocity_to_add += bodies_and_positions[i].0.compute_velocity(
-                    old_bodies_and_positions[i].1,
-                    old_bodies_and_positions[j].1,
-                    old_bodies_and_positions[j].0.mass,
-                    g,
-                    delta_seconds,
+        let collisions = simulation.advance(delta_seconds).collisions;
+        retain_survivors(&mut survivors, &collisions);
+        let mut positions = vec![None; entities.len()];
+        for (&body, simulated) in survivors.iter().zip(&simulation.bodies) {
+            positions[body] = Some(simulated.position);
+        }
+
+        for &body in &survivors {
+            if let (Some(old_position), Some(position)) =
+                (displayed(&old_positions, body), displayed(&positions, body))
+            {
+                gizmos.line(
+                    origin.to_render(old_position),
+                    origin.to_render(position),
+                    colors[body],
                 );
             }
-            bodies_and_positions[i].2 += total_velocity_to_add;
-            bodies_and_positions[i].1 =
-                bodies_and_positions[i].1 + bodies_and_positions[i].2 * delta_seconds;
-            gizmos.line(
-                old_bodies_and_positions[i].1,
-                bodies_and_positions[i].1,
-                Color::rgb_from_array(bodies_and_positions[i].0.color),
-            );
         }
+        old_positions = positions;
     }
 }
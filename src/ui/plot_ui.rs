@@ -0,0 +1,249 @@
+use std::{collections::VecDeque, fmt};
+
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+use egui_plot::{Legend, Line, Plot, PlotPoints};
+
+use super::selected_planet_ui::SelectedPlanetMarker;
+use crate::planets::planet_bundle::{CelestialBodyData, Position};
+use crate::planets::SimulationClock;
+use crate::simulation::{orbits, physics_config::PhysicsConfig};
+
+/// How many samples are kept per body.
+const MAX_SAMPLES: usize = 2_000;
+
+#[derive(Debug, Clone, Copy)]
+/// The state of a body relative to the reference body at some simulated time.
+struct Sample {
+    time: f64,
+    distance: f64,
+    speed: f64,
+    orbital_energy: f64,
+    eccentricity: f64,
+}
+
+#[derive(Debug, Clone, Copy)]
+/// A quantity plotted over time.
+enum PlottedQuantity {
+    Distance,
+    Speed,
+    OrbitalEnergy,
+    Eccentricity,
+}
+
+impl PlottedQuantity {
+    /// Every plotted quantity, in the order they are shown in the UI.
+    const ALL: [PlottedQuantity; 4] = [
+        PlottedQuantity::Distance,
+        PlottedQuantity::Speed,
+        PlottedQuantity::OrbitalEnergy,
+        PlottedQuantity::Eccentricity,
+    ];
+
+    /// The value of this quantity in `sample`.
+    fn of(self, sample: &Sample) -> f64 {
+        match self {
+            PlottedQuantity::Distance => sample.distance,
+            PlottedQuantity::Speed => sample.speed,
+            PlottedQuantity::OrbitalEnergy => sample.orbital_energy,
+            PlottedQuantity::Eccentricity => sample.eccentricity,
+        }
+    }
+}
+
+impl fmt::Display for PlottedQuantity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            PlottedQuantity::Distance => "Distance to reference",
+            PlottedQuantity::Speed => "Speed relative to reference",
+            PlottedQuantity::OrbitalEnergy => "Specific orbital energy",
+            PlottedQuantity::Eccentricity => "Eccentricity",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+#[derive(Component, Default)]
+/// The most recent samples of a body, oldest first.
+struct PlotHistory(VecDeque<Sample>);
+
+#[derive(Resource, Default)]
+/// Which bodies are plotted and what they are measured against.
+struct PlotSettings {
+    /// The body the others are measured against, the most massive one if unset.
+    reference: Option<Entity>,
+    /// The reference the histories were recorded against.
+    recorded_reference: Option<Entity>,
+    /// The bodies plotted along with the selected one.
+    pinned: Vec<Entity>,
+    /// The simulated time of the last samples, so paused frames are not sampled.
+    last_sampled: Option<f64>,
+}
+
+/// Plugin responsible for recording the history of the bodies and plotting it.
+pub struct PlotUiPlugin;
+
+impl Plugin for PlotUiPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<PlotSettings>().add_systems(
+            Update,
+            (add_histories, record_samples, display_plots).chain(),
+        );
+    }
+}
+
+/// Gives a history to the bodies that do not have one yet.
+fn add_histories(
+    mut commands: Commands,
+    query: Query<Entity, (With<CelestialBodyData>, Without<PlotHistory>)>,
+) {
+    for entity in &query {
+        commands.entity(entity).insert(PlotHistory::default());
+    }
+}
+
+/// Samples every body relative to the reference body, once per simulated time.
+fn record_samples(
+    mut query: Query<(Entity, &CelestialBodyData, &Position, &mut PlotHistory)>,
+    physics_config: Res<PhysicsConfig>,
+    clock: Res<SimulationClock>,
+    mut settings: ResMut<PlotSettings>,
+) {
+    let reference = settings
+        .reference
+        .filter(|&entity| query.contains(entity))
+        .or_else(|| {
+            query
+                .iter()
+                .max_by(|(_, a, _, _), (_, b, _, _)| a.mass.total_cmp(&b.mass))
+                .map(|(entity, _, _, _)| entity)
+        });
+    // samples relative to another body cannot be compared
+    if reference != settings.recorded_reference {
+        for (_, _, _, mut history) in &mut query {
+            history.0.clear();
+        }
+        settings.recorded_reference = reference;
+        settings.last_sampled = None;
+    }
+    if settings.last_sampled == Some(clock.elapsed) {
+        return;
+    }
+    let Some((reference_mass, reference_position, reference_velocity)) = reference
+        .and_then(|entity| query.get(entity).ok())
+        .map(|(_, data, position, _)| (data.mass, position.0, data.velocity))
+    else {
+        return;
+    };
+    settings.last_sampled = Some(clock.elapsed);
+
+    for (entity, data, position, mut history) in &mut query {
+        if Some(entity) == reference {
+            continue;
+        }
+        let relative_position = position.0 - reference_position;
+        let relative_velocity = data.velocity - reference_velocity;
+        let mu = orbits::gravitational_parameter(physics_config.g, reference_mass, data.mass);
+        history.0.push_back(Sample {
+            time: clock.elapsed,
+            distance: relative_position.length(),
+            speed: relative_velocity.length(),
+            orbital_energy: orbits::specific_orbital_energy(
+                relative_position,
+                relative_velocity,
+                mu,
+            ),
+            eccentricity: orbits::eccentricity_vector(relative_position, relative_velocity, mu)
+                .length(),
+        });
+        if history.0.len() > MAX_SAMPLES {
+            history.0.pop_front();
+        }
+    }
+}
+
+/// Displays the history of the selected and pinned bodies in a floating window.
+fn display_plots(
+    mut contexts: EguiContexts,
+    query: Query<(
+        Entity,
+        &CelestialBodyData,
+        &PlotHistory,
+        Has<SelectedPlanetMarker>,
+    )>,
+    mut settings: ResMut<PlotSettings>,
+) {
+    settings.pinned.retain(|&entity| query.contains(entity));
+
+    egui::Window::new("Plots")
+        .default_open(false)
+        .show(contexts.ctx_mut(), |ui| {
+            let reference_name = settings
+                .recorded_reference
+                .and_then(|entity| query.get(entity).ok())
+                .map(|(_, data, _, _)| data.name.clone())
+                .unwrap_or_default();
+            egui::ComboBox::from_label("Reference")
+                .selected_text(match settings.reference {
+                    Some(_) => reference_name,
+                    None => format!("Most massive ({})", reference_name),
+                })
+                .show_ui(ui, |ui| {
+                    ui.selectable_value(&mut settings.reference, None, "Most massive");
+                    for (entity, data, _, _) in &query {
+                        ui.selectable_value(&mut settings.reference, Some(entity), &data.name);
+                    }
+                });
+
+            ui.horizontal_wrapped(|ui| {
+                if let Some((selected, _, _, _)) =
+                    query.iter().find(|(_, _, _, selected)| *selected)
+                {
+                    if !settings.pinned.contains(&selected) && ui.button("Pin selected").clicked() {
+                        settings.pinned.push(selected);
+                    }
+                }
+                let mut unpinned = None;
+                for &entity in &settings.pinned {
+                    if let Ok((_, data, _, _)) = query.get(entity) {
+                        if ui.button(format!("{} ✖", data.name)).clicked() {
+                            unpinned = Some(entity);
+                        }
+                    }
+                }
+                settings.pinned.retain(|&entity| Some(entity) != unpinned);
+            });
+
+            let plotted = query
+                .iter()
+                .filter(|(entity, _, _, selected)| *selected || settings.pinned.contains(entity))
+                .collect::<Vec<_>>();
+            if plotted.is_empty() {
+                ui.label("Select or pin a body to plot it");
+                return;
+            }
+
+            for quantity in PlottedQuantity::ALL {
+                ui.label(quantity.to_string());
+                Plot::new(quantity.to_string())
+                    .height(120.)
+                    .legend(Legend::default())
+                    .link_axis("Plots", true, false)
+                    .show(ui, |plot_ui| {
+                        for (_, data, history, _) in &plotted {
+                            let [r, g, b, _] = Color::rgb_from_array(data.color).as_rgba_u8();
+                            let points = history
+                                .0
+                                .iter()
+                                .map(|sample| [sample.time, quantity.of(sample)])
+                                .collect::<PlotPoints>();
+                            plot_ui.line(
+                                Line::new(points)
+                                    .name(&data.name)
+                                    .color(egui::Color32::from_rgb(r, g, b)),
+                            );
+                        }
+                    });
+            }
+        });
+}
@@ -1,17 +1,32 @@
-use bevy::ecs::query;
+use std::collections::HashSet;
+
+use bevy::math::DVec3;
 use bevy::prelude::*;
-use bevy::window::PrimaryWindow;
 use bevy_egui::{egui, EguiContexts};
 
-use crate::planets::planet_bundle::{CelestialBodyBundle, CelestialBodyType};
+use crate::planets::planet_bundle::CelestialBodyData;
+use crate::planets::planet_bundle::{
+    spawn_body, Acceleration, CelestialBodyType, OrbitParent, Position,
+};
+use crate::simulation::physics_config::PhysicsConfig;
+use crate::ui::edit_history::{BodySnapshot, EditHistory};
 use crate::ui::AppConfig;
-use crate::{camera::MainCamera, planets::planet_bundle::CelestialBodyData};
+use group_ui::{display_group_window, GroupEdit};
+use orbit_ui::{orbital_elements_ui, OrbitPlacement};
+use selection::{
+    check_selection, delete_bodies, delete_selection_on_key, hover_bodies, HoveredBody,
+    SelectionDrag,
+};
+
+mod group_ui;
+mod orbit_ui;
+mod selection;
 
 #[derive(Resource, Default)]
 struct Duplicate(bool);
 
 #[derive(Component)]
-/// Marker component for the currently selected planet.
+/// Marker component for the currently selected planets.
 pub struct SelectedPlanetMarker;
 
 /// Plugin responsible for displaying the planets related UI.
@@ -20,85 +35,27 @@ pub struct SelectedPlanetUiPlugin;
 
 impl Plugin for SelectedPlanetUiPlugin {
     fn build(&self, app: &mut App) {
-        app.init_resource::<Duplicate>().add_systems(
-            Update,
-            (
-                check_selection,
-                display_selected_planet_window,
-                add_new_planet.run_if(run_if_add_new_planet),
-                duplicate_planet.run_if(run_if_duplicate_planet),
-            ),
-        );
-    }
-}
-
-/// Clears the selected planet.
-fn clear_selection(
-    commands: &mut Commands,
-    selected: Result<(Entity, Mut<CelestialBodyData>, &Transform), query::QuerySingleError>,
-) {
-    if let Ok((e, _, _)) = selected {
-        commands
-            .get_entity(e)
-            .unwrap()
-            .remove::<SelectedPlanetMarker>();
-    }
-}
-
-/// Checks if the user has clicked on a planet and selects it.
-#[allow(clippy::type_complexity)]
-fn check_selection(
-    mut contexts: EguiContexts,
-    mut commands: Commands,
-    mut query: Query<
-        (Entity, &mut CelestialBodyData, &Transform),
-        (With<CelestialBodyData>, Without<SelectedPlanetMarker>),
-    >,
-    mut query_selected: Query<
-        (Entity, &mut CelestialBodyData, &Transform),
-        With<SelectedPlanetMarker>,
-    >,
-    mouse_button_input: Res<ButtonInput<MouseButton>>,
-    camera_query: Query<(&Camera, &GlobalTransform), With<MainCamera>>,
-    q_windows: Query<&Window, With<PrimaryWindow>>,
-) {
-    if !mouse_button_input.just_pressed(MouseButton::Left)
-        || contexts.ctx_mut().wants_pointer_input()
-        || contexts.ctx_mut().wants_keyboard_input()
-    {
-        return;
-    }
-    let (camera, camera_transform) = camera_query.single();
-
-    let Some(cursor_position) = q_windows.single().cursor_position() else {
-        return;
-    };
-    let Some(ray) = camera.viewport_to_world(camera_transform, cursor_position) else {
-        return;
-    };
-
-    for (e, planet, transform) in &mut query {
-        let l = ray.origin - transform.translation;
-        if ray.direction.dot(l).abs() < 0. {
-            continue;
-        }
-
-        let h = ray.origin
-            + ray.direction.dot(l) * ray.direction.as_dvec3().as_vec3()
-                / (ray.direction.length() * ray.direction.length());
-
-        let d = (l.length_squared() - (h - ray.origin).length_squared()).sqrt();
-
-        if d < planet.radius {
-            clear_selection(&mut commands, query_selected.get_single_mut());
-            commands.get_entity(e).unwrap().insert(SelectedPlanetMarker);
-            return;
-        }
+        app.init_resource::<Duplicate>()
+            .init_resource::<OrbitPlacement>()
+            .init_resource::<SelectionDrag>()
+            .init_resource::<HoveredBody>()
+            .init_resource::<GroupEdit>()
+            .add_systems(
+                Update,
+                (
+                    (hover_bodies, check_selection).chain(),
+                    delete_selection_on_key,
+                    display_selected_planet_window,
+                    display_group_window,
+                    add_new_planet.run_if(run_if_add_new_planet),
+                    duplicate_planet.run_if(run_if_duplicate_planet),
+                ),
+            );
     }
 }
 
 /// Displays the selected planet's data in a floating window.
-#[allow(clippy::too_many_arguments)]
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
 fn display_selected_planet_window(
     mut commands: Commands,
     mut duplicate: ResMut<Duplicate>,
@@ -107,57 +64,95 @@ fn display_selected_planet_window(
         (
             Entity,
             &mut CelestialBodyData,
-            &mut Transform,
+            &Transform,
+            &mut Position,
+            &Acceleration,
+            &mut OrbitParent,
             Option<&mut Children>,
         ),
         With<SelectedPlanetMarker>,
     >,
+    query_others: Query<(Entity, &CelestialBodyData, &Position), Without<SelectedPlanetMarker>>,
     mut query_child: Query<&mut PointLight>,
     mut standard_materials: Query<&mut Handle<StandardMaterial>>,
     mut materials: ResMut<Assets<StandardMaterial>>,
+    mut orbit_placement: ResMut<OrbitPlacement>,
+    mut history: ResMut<EditHistory>,
+    physics_config: Res<PhysicsConfig>,
     mut gizmos: Gizmos,
 ) {
+    let units = physics_config.unit_system;
+
     // show selection by drawing unit vectors on the selection
-    for (_, body_data, transform, _) in &query_selected_data {
+    for (_, body_data, transform, _, _, _, _) in &query_selected_data {
         let body_position = transform.translation;
+        let radius = body_data.radius as f32;
 
         gizmos.arrow(
             body_position,
-            body_position + Vec3::X * 2. * body_data.radius,
+            body_position + Vec3::X * 2. * radius,
             Color::RED,
         );
 
         gizmos.arrow(
             body_position,
-            body_position + Vec3::Y * 2. * body_data.radius,
+            body_position + Vec3::Y * 2. * radius,
             Color::GREEN,
         );
 
         gizmos.arrow(
             body_position,
-            body_position + Vec3::Z * 2. * body_data.radius,
+            body_position + Vec3::Z * 2. * radius,
             Color::BLUE,
         );
     }
 
     // selection window
-    if let Ok((entity, mut planet, mut tfm, children)) = query_selected_data.get_single_mut() {
+    if let Ok((entity, mut planet, _, mut position, acceleration, mut orbit_parent, children)) =
+        query_selected_data.get_single_mut()
+    {
+        let before = BodySnapshot::new(&planet, &position, &orbit_parent);
+        let mut delete = false;
         egui::Window::new(planet.name.clone()).show(contexts.ctx_mut(), |ui| {
-            // Duplicate
-            if ui.button("Duplicate").clicked() {
-                duplicate.0 = true;
-            }
+            ui.horizontal(|ui| {
+                // Duplicate
+                if ui.button("Duplicate").clicked() {
+                    duplicate.0 = true;
+                }
+                delete = ui.button("Delete").clicked();
+            });
 
             // Properties
             ui.horizontal(|ui| {
                 ui.add(egui::DragValue::new(&mut planet.radius));
-                ui.label("Radius");
+                ui.label(with_unit("Radius", units.length_unit()));
             });
 
             ui.horizontal(|ui| {
                 let speed = (planet.mass / 10.0 + 1.0).abs();
-                ui.add(egui::DragValue::new(&mut planet.mass).speed(speed));
-                ui.label("Mass");
+                // test particles stay massless
+                ui.add_enabled(
+                    planet.body_type != CelestialBodyType::TestParticle,
+                    egui::DragValue::new(&mut planet.mass).speed(speed),
+                );
+                ui.label(with_unit("Mass", units.mass_unit()));
+            });
+
+            ui.horizontal(|ui| {
+                let mut custom_softening = planet.softening.is_some();
+                if ui
+                    .checkbox(&mut custom_softening, "Custom softening")
+                    .changed()
+                {
+                    planet.softening = custom_softening.then_some(physics_config.softening);
+                }
+                if let Some(softening) = planet.softening.as_mut() {
+                    ui.add(
+                        egui::DragValue::new(softening)
+                            .speed(0.01)
+                            .clamp_range(0.0..=f64::INFINITY),
+                    );
+                }
             });
 
             // Color & Emissive factor
@@ -178,12 +173,7 @@ fn display_selected_planet_window(
                     .changed()
             {
                 if let Ok(handle) = standard_materials.get_mut(entity) {
-                    materials.get_mut(handle.id()).unwrap().base_color =
-                        Color::rgb_from_array(planet.color);
-                }
-                if let Ok(handle) = standard_materials.get_mut(entity) {
-                    let m = materials.get_mut(handle.id()).unwrap();
-                    m.emissive = m.base_color * planet.emissive_factor;
+                    *materials.get_mut(handle.id()).unwrap() = planet.material();
                 }
             }
 
@@ -204,61 +194,78 @@ fn display_selected_planet_window(
 
             // Body type
             egui::ComboBox::from_label("Type")
-                .selected_text(format!("{:?}", planet.body_type))
+                .selected_text(planet.body_type.to_string())
                 .show_ui(ui, |ui| {
-                    if ui
-                        .selectable_value(
-                            &mut planet.body_type,
-                            CelestialBodyType::Planet,
-                            "Planet",
-                        )
-                        .clicked()
-                    {
-                        if let Ok(handle) = standard_materials.get_mut(entity) {
-                            let m = materials.get_mut(handle.id()).unwrap();
-                            m.emissive = Color::BLACK;
+                    for body_type in CelestialBodyType::ALL {
+                        if ui
+                            .selectable_label(planet.body_type == body_type, body_type.to_string())
+                            .clicked()
+                            && planet.body_type != body_type
+                        {
+                            planet.set_body_type(body_type);
+                            if let Ok(handle) = standard_materials.get_mut(entity) {
+                                *materials.get_mut(handle.id()).unwrap() = planet.material();
+                            }
+
+                            commands.entity(entity).despawn_descendants();
+                            if let Some(point_light) = planet.point_light() {
+                                commands.entity(entity).with_children(|p| {
+                                    p.spawn(point_light);
+                                });
+                            }
                         }
-                        commands.entity(entity).despawn_descendants();
-                    }
-
-                    if ui
-                        .selectable_value(&mut planet.body_type, CelestialBodyType::Star, "Star")
-                        .clicked()
-                    {
-                        if let Ok(handle) = standard_materials.get_mut(entity) {
-                            let m = materials.get_mut(handle.id()).unwrap();
-                            m.emissive = m.base_color * planet.emissive_factor;
-                        }
-
-                        commands.entity(entity).with_children(|p| {
-                            p.spawn(PointLightBundle {
-                                point_light: PointLight {
-                                    color: Color::WHITE,
-                                    intensity: planet.light_factor,
-                                    range: 1000.0,
-                                    radius: planet.radius,
-                                    ..default()
-                                },
-                                ..default()
-                            });
-                        });
                     }
                 });
 
             ui.horizontal(|ui| {
-                ui.add(egui::DragValue::new(&mut tfm.translation.x));
-                ui.add(egui::DragValue::new(&mut tfm.translation.y));
-                ui.add(egui::DragValue::new(&mut tfm.translation.z));
-                ui.label("Position");
+                ui.add(egui::DragValue::new(&mut position.0.x));
+                ui.add(egui::DragValue::new(&mut position.0.y));
+                ui.add(egui::DragValue::new(&mut position.0.z));
+                ui.label(with_unit("Position", units.length_unit()));
             });
 
             ui.horizontal(|ui| {
                 ui.add(egui::DragValue::new(&mut planet.velocity.x));
                 ui.add(egui::DragValue::new(&mut planet.velocity.y));
                 ui.add(egui::DragValue::new(&mut planet.velocity.z));
-                ui.label("Velocity");
-            })
+                ui.label(with_unit("Velocity", units.velocity_unit()));
+            });
+
+            ui.label(format!(
+                "Acceleration : ({:.3}, {:.3}, {:.3})",
+                acceleration.0.x, acceleration.0.y, acceleration.0.z
+            ));
+
+            ui.collapsing("Orbit", |ui| {
+                orbital_elements_ui(
+                    ui,
+                    entity,
+                    &mut planet,
+                    &mut position,
+                    &query_others,
+                    &mut orbit_parent,
+                    &mut orbit_placement,
+                    &physics_config,
+                );
+            });
         });
+
+        let after = BodySnapshot::new(&planet, &position, &orbit_parent);
+        let ctx = contexts.ctx_mut();
+        let editing = ctx.dragged_id().is_some() || ctx.wants_keyboard_input();
+        history.modify(entity, before, after.clone(), editing);
+        if delete {
+            delete_bodies(&mut commands, &mut history, vec![(entity, after)]);
+        }
+    }
+}
+
+/// Appends a unit to a label, unless the unit system is unitless.
+fn with_unit(label: &str, unit: &str) -> String {
+    if unit.is_empty() {
+        label.to_string()
+    } else {
+        format!("{} ({})", label, unit)
     }
 }
 
@@ -269,12 +276,13 @@ fn run_if_add_new_planet(app_config: Res<AppConfig>) -> bool {
 fn add_new_planet(
     mut app_config: ResMut<AppConfig>,
     mut commands: Commands,
-    query: Query<(&Transform, &CelestialBodyData)>,
-    mut query_selected: Query<Entity, With<SelectedPlanetMarker>>,
+    query: Query<(&Position, &CelestialBodyData)>,
+    query_selected: Query<Entity, With<SelectedPlanetMarker>>,
     mut materials: ResMut<Assets<StandardMaterial>>,
     mut meshes: ResMut<Assets<Mesh>>,
+    mut history: ResMut<EditHistory>,
 ) {
-    if let Ok(entity) = query_selected.get_single_mut() {
+    for entity in &query_selected {
         commands.entity(entity).remove::<SelectedPlanetMarker>();
     }
 
@@ -283,27 +291,25 @@ fn add_new_planet(
         rand::random::<f32>(),
         rand::random::<f32>(),
     ]);
-    let material = materials.add(StandardMaterial {
-        base_color: color,
-        ..Default::default()
-    });
 
     app_config.add_new_planet = false;
 
-    let mesh = meshes.add(Sphere::new(1.0).mesh().ico(5).unwrap());
-
     let mut radius = 0.;
     let mut mass = 0.;
-    let mut position = Vec3::ZERO;
-    let mut velocity = Vec3::ZERO;
-    for (tfm, data) in &query {
-        radius += data.radius;
-        mass += data.mass;
-        position += tfm.translation;
+    let mut position = DVec3::ZERO;
+    let mut velocity = DVec3::ZERO;
+    for (body_position, data) in &query {
+        // averaged as planets of the same scale, whatever their types
+        let (body_mass, body_radius) =
+            data.body_type
+                .rescale(CelestialBodyType::Planet, data.mass, data.radius);
+        radius += body_radius;
+        mass += body_mass;
+        position += body_position.0;
         velocity += data.velocity;
     }
 
-    let cnt = query.iter().count() as f32;
+    let cnt = query.iter().count() as f64;
 
     if cnt > 0.0 {
         radius /= cnt;
@@ -311,95 +317,94 @@ fn add_new_planet(
         position /= cnt;
         velocity /= cnt;
     } else {
-        radius = 3.;
-        mass = 10.;
+        (mass, radius) = CelestialBodyType::Planet.default_mass_and_radius();
     }
-    commands.spawn((
-        CelestialBodyBundle {
-            pbr: PbrBundle {
-                mesh,
-                material,
-                transform: Transform {
-                    translation: position
-                        + if cnt == 1.0 {
-                            Vec3::ONE * radius
-                        } else {
-                            Vec3::ZERO
-                        },
-                    scale: Vec3::ONE * radius,
-                    ..default()
-                },
-                ..Default::default()
-            },
-
-            body_data: CelestialBodyData::new(
-                String::from("New Planet"),
-                CelestialBodyType::Planet,
-                mass,
-                radius,
-                velocity,
-                color,
-            ),
-        },
-        SelectedPlanetMarker,
-    ));
+    let body_data = CelestialBodyData::new(
+        String::from("New Planet"),
+        CelestialBodyType::Planet,
+        mass,
+        radius,
+        velocity,
+        color,
+    );
+    let position = position
+        + if cnt == 1.0 {
+            DVec3::ONE * radius
+        } else {
+            DVec3::ZERO
+        };
+    let snapshot = BodySnapshot {
+        data: body_data.clone(),
+        position,
+        orbit_parent: OrbitParent::default(),
+    };
+    let entity = spawn_body(
+        &mut commands,
+        &mut meshes,
+        &mut materials,
+        body_data,
+        position,
+        OrbitParent::default(),
+    );
+    commands.entity(entity).insert(SelectedPlanetMarker);
+    history.spawn(String::from("Add New Planet"), vec![(entity, snapshot)]);
 }
 
 fn run_if_duplicate_planet(duplicate: Res<Duplicate>) -> bool {
     duplicate.0
 }
 
+/// Duplicates the selected bodies, the copies becoming the selection.
+///
+/// The copies keep everything but their names, suffixed to stay unique.
 fn duplicate_planet(
     mut duplicate: ResMut<Duplicate>,
     mut commands: Commands,
     selected_query: Query<
-        (&Transform, &Handle<Mesh>, &CelestialBodyData),
+        (Entity, &Position, &CelestialBodyData, &OrbitParent),
         With<SelectedPlanetMarker>,
     >,
-    mut selected_entity_query: Query<Entity, With<SelectedPlanetMarker>>,
+    names_query: Query<&CelestialBodyData>,
     mut materials: ResMut<Assets<StandardMaterial>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut history: ResMut<EditHistory>,
 ) {
-    let color = Color::rgb_from_array([
-        rand::random::<f32>(),
-        rand::random::<f32>(),
-        rand::random::<f32>(),
-    ]);
-    let material = materials.add(StandardMaterial {
-        base_color: color,
-        ..Default::default()
-    });
-
     duplicate.0 = false;
 
-    if let Ok((tfm, mesh, data)) = selected_query.get_single() {
-        commands.spawn((
-            CelestialBodyBundle {
-                pbr: PbrBundle {
-                    mesh: mesh.clone(),
-                    material,
-                    transform: Transform {
-                        translation: tfm.translation + Vec3::ONE * data.radius,
-                        scale: Vec3::ONE * data.radius,
-                        ..default()
-                    },
-                    ..Default::default()
-                },
-
-                body_data: CelestialBodyData::new(
-                    String::from("Planet"),
-                    CelestialBodyType::Planet,
-                    data.mass,
-                    data.radius,
-                    data.velocity,
-                    color,
-                ),
-            },
-            SelectedPlanetMarker,
-        ));
+    let mut taken = names_query
+        .iter()
+        .map(|data| data.name.clone())
+        .collect::<HashSet<_>>();
+    let mut spawned = Vec::new();
+    for (selected, position, data, orbit_parent) in &selected_query {
+        let mut body_data = data.clone();
+        body_data.name = std::iter::once(format!("{} (copy)", data.name))
+            .chain((2..).map(|n| format!("{} (copy {})", data.name, n)))
+            .find(|name| !taken.contains(name))
+            .unwrap();
+        taken.insert(body_data.name.clone());
+        let position = position.0 + DVec3::ONE * data.radius;
+        let snapshot = BodySnapshot {
+            data: body_data.clone(),
+            position,
+            orbit_parent: *orbit_parent,
+        };
+        let entity = spawn_body(
+            &mut commands,
+            &mut meshes,
+            &mut materials,
+            body_data,
+            position,
+            *orbit_parent,
+        );
+        commands.entity(entity).insert(SelectedPlanetMarker);
+        commands.entity(selected).remove::<SelectedPlanetMarker>();
+        spawned.push((entity, snapshot));
     }
 
-    // since we're in duplicate, this should always be Ok(_)
-    if let Ok(entity) = selected_entity_query.get_single_mut() {
-        commands.entity(entity).remove::<SelectedPlanetMarker>();
-    }
+    let label = match selected_query.iter().next() {
+        Some((_, _, data, _)) if spawned.len() == 1 => format!("Duplicate {}", data.name),
+        _ => format!("Duplicate {} bodies", spawned.len()),
+    };
+    history.spawn(label, spawned);
 }
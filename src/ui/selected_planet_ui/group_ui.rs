@@ -0,0 +1,117 @@
+use bevy::math::DVec3;
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+
+use super::selection::delete_bodies;
+use super::{with_unit, Duplicate, SelectedPlanetMarker};
+use crate::planets::planet_bundle::{CelestialBodyData, OrbitParent, Position};
+use crate::simulation::physics_config::PhysicsConfig;
+use crate::ui::edit_history::{BodySnapshot, EditHistory};
+
+#[derive(Resource)]
+/// The values applied to every selected body by the selection window.
+pub(super) struct GroupEdit {
+    velocity_shift: DVec3,
+    color: [f32; 3],
+}
+
+impl Default for GroupEdit {
+    fn default() -> Self {
+        Self {
+            velocity_shift: DVec3::ZERO,
+            color: [1., 1., 1.],
+        }
+    }
+}
+
+/// Displays the operations applying to every body of a multi-selection.
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+pub(super) fn display_group_window(
+    mut contexts: EguiContexts,
+    mut commands: Commands,
+    mut duplicate: ResMut<Duplicate>,
+    mut group_edit: ResMut<GroupEdit>,
+    mut query_selected: Query<
+        (
+            Entity,
+            &mut CelestialBodyData,
+            &Position,
+            &OrbitParent,
+            &Handle<StandardMaterial>,
+        ),
+        With<SelectedPlanetMarker>,
+    >,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut history: ResMut<EditHistory>,
+    physics_config: Res<PhysicsConfig>,
+) {
+    let count = query_selected.iter().count();
+    if count < 2 {
+        return;
+    }
+    let units = physics_config.unit_system;
+
+    let (mut delete, mut deselect, mut shift_velocity, mut set_color) =
+        (false, false, false, false);
+    egui::Window::new("Selection").show(contexts.ctx_mut(), |ui| {
+        ui.label(format!("{} bodies selected", count));
+        ui.horizontal(|ui| {
+            if ui.button("Duplicate").clicked() {
+                duplicate.0 = true;
+            }
+            delete = ui.button("Delete").clicked();
+            deselect = ui.button("Deselect").clicked();
+        });
+        ui.separator();
+
+        ui.horizontal(|ui| {
+            ui.add(egui::DragValue::new(&mut group_edit.velocity_shift.x));
+            ui.add(egui::DragValue::new(&mut group_edit.velocity_shift.y));
+            ui.add(egui::DragValue::new(&mut group_edit.velocity_shift.z));
+            ui.label(with_unit("Velocity shift", units.velocity_unit()));
+            shift_velocity = ui.button("Apply").clicked();
+        });
+
+        ui.horizontal(|ui| {
+            ui.color_edit_button_rgb(&mut group_edit.color);
+            ui.label("Color");
+            set_color = ui.button("Apply").clicked();
+        });
+    });
+
+    if delete {
+        let bodies = query_selected
+            .iter()
+            .map(|(entity, data, position, orbit_parent, _)| {
+                (entity, BodySnapshot::new(data, position, orbit_parent))
+            })
+            .collect();
+        delete_bodies(&mut commands, &mut history, bodies);
+    } else if deselect {
+        for (entity, ..) in &query_selected {
+            commands.entity(entity).remove::<SelectedPlanetMarker>();
+        }
+    } else if shift_velocity || set_color {
+        let changes = query_selected
+            .iter_mut()
+            .map(|(entity, mut data, position, orbit_parent, material)| {
+                let before = BodySnapshot::new(&data, position, orbit_parent);
+                if shift_velocity {
+                    data.velocity += group_edit.velocity_shift;
+                }
+                if set_color {
+                    data.color = group_edit.color;
+                    if let Some(material) = materials.get_mut(material.id()) {
+                        *material = data.material();
+                    }
+                }
+                (
+                    entity,
+                    before,
+                    BodySnapshot::new(&data, position, orbit_parent),
+                )
+            })
+            .collect();
+        history.modify_many(changes, false);
+    }
+}
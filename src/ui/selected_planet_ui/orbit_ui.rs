@@ -0,0 +1,219 @@
+use bevy::prelude::*;
+use bevy_egui::egui;
+
+use super::{with_unit, SelectedPlanetMarker};
+use crate::planets::planet_bundle::{CelestialBodyData, OrbitParent, Position};
+use crate::simulation::{
+    orbits::{gravitational_parameter, OrbitalElements},
+    physics_config::PhysicsConfig,
+};
+
+#[derive(Resource, Default)]
+/// The orbit the selected body is placed on by the "Place" button.
+pub struct OrbitPlacement {
+    /// The body and parent the distance was taken for.
+    placed: Option<(Entity, Option<Entity>)>,
+    /// The distance to the parent at periapsis, the current distance if unset.
+    distance: Option<f64>,
+    /// 0 for a circular orbit.
+    eccentricity: f64,
+    inclination: f64,
+    longitude_of_ascending_node: f64,
+    /// Where the body is placed around the parent, from the ascending node.
+    argument_of_periapsis: f64,
+}
+
+/// Edits an angle stored in radians with a `DragValue` in degrees, on a row of
+/// a grid. Returns true if it changed.
+fn angle_row(ui: &mut egui::Ui, value: &mut f64, label: &str) -> bool {
+    let mut degrees = value.to_degrees();
+    let changed = ui
+        .add(egui::DragValue::new(&mut degrees).speed(0.5).suffix("°"))
+        .changed();
+    if changed {
+        *value = degrees.to_radians();
+    }
+    ui.label(label);
+    ui.end_row();
+    changed
+}
+
+/// Shows the orbital elements of the selected body relative to its parent,
+/// regenerating its position and velocity when they are edited, and a helper
+/// placing the body on a new orbit around the parent.
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+pub fn orbital_elements_ui(
+    ui: &mut egui::Ui,
+    entity: Entity,
+    body: &mut CelestialBodyData,
+    position: &mut Position,
+    others: &Query<(Entity, &CelestialBodyData, &Position), Without<SelectedPlanetMarker>>,
+    orbit_parent: &mut OrbitParent,
+    placement: &mut OrbitPlacement,
+    physics_config: &PhysicsConfig,
+) {
+    let units = physics_config.unit_system;
+    let parent_name = |parent: Option<Entity>| {
+        parent
+            .and_then(|entity| others.get(entity).ok())
+            .map(|(_, data, _)| data.name.clone())
+            .unwrap_or_else(|| String::from("nothing"))
+    };
+    egui::ComboBox::from_label("Orbits")
+        .selected_text(if orbit_parent.pinned {
+            parent_name(orbit_parent.parent)
+        } else {
+            format!("Automatic ({})", parent_name(orbit_parent.parent))
+        })
+        .show_ui(ui, |ui| {
+            if ui
+                .selectable_label(!orbit_parent.pinned, "Automatic")
+                .clicked()
+            {
+                orbit_parent.pinned = false;
+            }
+            for (entity, data, _) in others {
+                if ui
+                    .selectable_label(
+                        orbit_parent.pinned && orbit_parent.parent == Some(entity),
+                        &data.name,
+                    )
+                    .clicked()
+                {
+                    *orbit_parent = OrbitParent {
+                        parent: Some(entity),
+                        pinned: true,
+                    };
+                }
+            }
+        });
+
+    let Some((_, primary_data, primary_position)) = orbit_parent
+        .parent
+        .and_then(|entity| others.get(entity).ok())
+    else {
+        ui.label("Pick a body to orbit to see or set the orbit");
+        return;
+    };
+
+    let mu = gravitational_parameter(physics_config.g, primary_data.mass, body.mass);
+    let mut elements = OrbitalElements::from_state_vectors(
+        position.0 - primary_position.0,
+        body.velocity - primary_data.velocity,
+        mu,
+    );
+
+    let mut changed = false;
+    egui::Grid::new("Orbital elements")
+        .num_columns(2)
+        .show(ui, |ui| {
+            let speed = elements.semi_major_axis.abs() / 100.;
+            changed |= ui
+                .add(egui::DragValue::new(&mut elements.semi_major_axis).speed(speed))
+                .changed();
+            ui.label(with_unit("Semi-major axis", units.length_unit()));
+            ui.end_row();
+
+            changed |= ui
+                .add(
+                    egui::DragValue::new(&mut elements.eccentricity)
+                        .speed(0.001)
+                        .clamp_range(0.0..=f64::INFINITY),
+                )
+                .changed();
+            ui.label("Eccentricity");
+            ui.end_row();
+
+            changed |= angle_row(ui, &mut elements.inclination, "Inclination");
+            changed |= angle_row(
+                ui,
+                &mut elements.longitude_of_ascending_node,
+                "Longitude of ascending node",
+            );
+            changed |= angle_row(
+                ui,
+                &mut elements.argument_of_periapsis,
+                "Argument of periapsis",
+            );
+            changed |= angle_row(ui, &mut elements.true_anomaly, "True anomaly");
+        });
+
+    ui.label(match elements.period(mu) {
+        Some(period) => format!("Period : {:.3}{}", period, units.time_unit()),
+        None => String::from("Period : unbound orbit"),
+    });
+
+    if changed {
+        match elements.to_state_vectors(mu) {
+            Some((relative_position, relative_velocity)) => {
+                position.0 = primary_position.0 + relative_position;
+                body.velocity = primary_data.velocity + relative_velocity;
+            }
+            None => {
+                ui.label("These elements do not describe an orbit");
+            }
+        }
+    }
+
+    ui.separator();
+    let placed = Some((entity, orbit_parent.parent));
+    if placement.placed != placed {
+        placement.placed = placed;
+        placement.distance = None;
+    }
+    let distance = placement
+        .distance
+        .get_or_insert_with(|| position.0.distance(primary_position.0));
+    egui::Grid::new("Orbit placement")
+        .num_columns(2)
+        .show(ui, |ui| {
+            let speed = *distance / 100.;
+            ui.add(
+                egui::DragValue::new(distance)
+                    .speed(speed)
+                    .clamp_range(0.0..=f64::INFINITY),
+            );
+            ui.label(with_unit("Distance", units.length_unit()));
+            ui.end_row();
+
+            ui.add(
+                egui::DragValue::new(&mut placement.eccentricity)
+                    .speed(0.001)
+                    .clamp_range(0.0..=0.999),
+            );
+            ui.label("Eccentricity");
+            ui.end_row();
+
+            angle_row(ui, &mut placement.inclination, "Inclination");
+            angle_row(
+                ui,
+                &mut placement.longitude_of_ascending_node,
+                "Longitude of ascending node",
+            );
+            angle_row(
+                ui,
+                &mut placement.argument_of_periapsis,
+                "Argument of periapsis",
+            );
+        });
+
+    if ui
+        .button(format!("Place in orbit around {}", primary_data.name))
+        .clicked()
+    {
+        let elements = OrbitalElements::at_periapsis(
+            placement.distance.unwrap_or_default(),
+            placement.eccentricity,
+            placement.inclination,
+            placement.longitude_of_ascending_node,
+            placement.argument_of_periapsis,
+        );
+        match elements.to_state_vectors(mu) {
+            Some((relative_position, relative_velocity)) => {
+                position.0 = primary_position.0 + relative_position;
+                body.velocity = primary_data.velocity + relative_velocity;
+            }
+            None => info!("Cannot place {} at a null distance", body.name),
+        }
+    }
+}
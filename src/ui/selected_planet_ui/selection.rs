@@ -0,0 +1,268 @@
+use bevy::prelude::*;
+use bevy::window::PrimaryWindow;
+use bevy_egui::{egui, EguiContexts};
+
+use super::SelectedPlanetMarker;
+use crate::camera::MainCamera;
+use crate::planets::planet_bundle::{CelestialBodyData, OrbitParent, Position};
+use crate::ui::edit_history::{BodySnapshot, EditHistory};
+use crate::ui::AppConfig;
+
+/// How far, in logical pixels, the cursor must move while the button is held
+/// for a click to become a box selection.
+const DRAG_THRESHOLD: f32 = 4.;
+
+#[derive(Resource, Default)]
+/// Where the left mouse button was pressed on the scene, while it is held.
+pub(super) struct SelectionDrag(Option<Vec2>);
+
+/// Whether one of the Shift keys is held.
+fn shift_pressed(keys: &ButtonInput<KeyCode>) -> bool {
+    keys.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight])
+}
+
+/// Adds or removes the selection marker of a body if needed.
+fn set_selected(commands: &mut Commands, entity: Entity, selected: bool, select: bool) {
+    if select && !selected {
+        commands.entity(entity).insert(SelectedPlanetMarker);
+    } else if !select && selected {
+        commands.entity(entity).remove::<SelectedPlanetMarker>();
+    }
+}
+
+/// The distance along `ray` to its first intersection with a sphere which is
+/// in front of the ray's origin.
+fn ray_sphere_intersection(ray: Ray3d, center: Vec3, radius: f32) -> Option<f32> {
+    let offset = ray.origin - center;
+    let b = ray.direction.dot(offset);
+    let discriminant = b * b - (offset.length_squared() - radius * radius);
+    if discriminant < 0. {
+        return None;
+    }
+    let root = discriminant.sqrt();
+    // only the far intersection is in front when the camera is inside the sphere
+    [-b - root, -b + root].into_iter().find(|&t| t >= 0.)
+}
+
+/// The radius of a body on screen, in logical pixels.
+fn screen_radius(
+    camera: &Camera,
+    camera_transform: &GlobalTransform,
+    center: Vec3,
+    radius: f32,
+) -> Option<(Vec2, f32)> {
+    let screen_center = camera.world_to_viewport(camera_transform, center)?;
+    let edge =
+        camera.world_to_viewport(camera_transform, center + camera_transform.right() * radius)?;
+    Some((screen_center, screen_center.distance(edge)))
+}
+
+/// Returns the body under the cursor which is the closest to the camera.
+///
+/// Bodies smaller than `min_pick_radius` pixels on screen are picked as if
+/// they were that large, so that distant ones remain clickable.
+fn pick<'a>(
+    camera: &Camera,
+    camera_transform: &GlobalTransform,
+    cursor_position: Vec2,
+    min_pick_radius: f32,
+    bodies: impl Iterator<Item = (Entity, &'a CelestialBodyData, &'a Transform)>,
+) -> Option<Entity> {
+    let ray = camera.viewport_to_world(camera_transform, cursor_position)?;
+    bodies
+        .filter_map(|(entity, planet, transform)| {
+            let center = transform.translation;
+            let radius = planet.radius as f32;
+            let depth = ray_sphere_intersection(ray, center, radius).or_else(|| {
+                let (screen_center, _) = screen_radius(camera, camera_transform, center, radius)?;
+                (screen_center.distance(cursor_position) <= min_pick_radius)
+                    .then(|| ray.direction.dot(center - ray.origin) - radius)
+            })?;
+            Some((entity, depth))
+        })
+        .min_by(|(_, a), (_, b)| a.total_cmp(b))
+        .map(|(entity, _)| entity)
+}
+
+#[derive(Resource, Default)]
+/// The body under the cursor, which a click would select.
+pub(super) struct HoveredBody(Option<Entity>);
+
+/// Finds the body under the cursor and highlights it.
+#[allow(clippy::type_complexity)]
+pub(super) fn hover_bodies(
+    mut contexts: EguiContexts,
+    mut hovered: ResMut<HoveredBody>,
+    query: Query<(Entity, &CelestialBodyData, &Transform)>,
+    camera_query: Query<(&Camera, &GlobalTransform), With<MainCamera>>,
+    q_windows: Query<&Window, With<PrimaryWindow>>,
+    app_config: Res<AppConfig>,
+) {
+    let ctx = contexts.ctx_mut();
+    let (camera, camera_transform) = camera_query.single();
+    hovered.0 = q_windows
+        .single()
+        .cursor_position()
+        .filter(|_| !ctx.is_pointer_over_area())
+        .and_then(|cursor_position| {
+            pick(
+                camera,
+                camera_transform,
+                cursor_position,
+                app_config.min_pick_radius,
+                query.iter(),
+            )
+        });
+
+    let Some((_, planet, transform)) = hovered.0.and_then(|entity| query.get(entity).ok()) else {
+        return;
+    };
+    let Some((center, radius)) = screen_radius(
+        camera,
+        camera_transform,
+        transform.translation,
+        planet.radius as f32,
+    ) else {
+        return;
+    };
+    let radius = radius.max(app_config.min_pick_radius) + 3.;
+    let painter = ctx.layer_painter(egui::LayerId::background());
+    let center = egui::pos2(center.x, center.y);
+    painter.circle_stroke(
+        center,
+        radius,
+        egui::Stroke::new(1.5, egui::Color32::YELLOW),
+    );
+    painter.text(
+        center + egui::vec2(radius + 4., -radius),
+        egui::Align2::LEFT_BOTTOM,
+        &planet.name,
+        egui::FontId::proportional(14.),
+        egui::Color32::YELLOW,
+    );
+}
+
+/// Selects the hovered body when clicked, or the bodies inside the box
+/// dragged with the left mouse button. Holding Shift adds to the selection, clicking an
+/// already selected body with Shift deselects it. Clicking empty space clears the selection.
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+pub(super) fn check_selection(
+    mut contexts: EguiContexts,
+    mut commands: Commands,
+    mut drag: ResMut<SelectionDrag>,
+    hovered: Res<HoveredBody>,
+    query: Query<(
+        Entity,
+        &CelestialBodyData,
+        &Transform,
+        Has<SelectedPlanetMarker>,
+    )>,
+    mouse_button_input: Res<ButtonInput<MouseButton>>,
+    keys: Res<ButtonInput<KeyCode>>,
+    camera_query: Query<(&Camera, &GlobalTransform), With<MainCamera>>,
+    q_windows: Query<&Window, With<PrimaryWindow>>,
+) {
+    let ctx = contexts.ctx_mut();
+    let cursor_position = q_windows.single().cursor_position();
+
+    if mouse_button_input.just_pressed(MouseButton::Left) {
+        drag.0 =
+            cursor_position.filter(|_| !ctx.wants_pointer_input() && !ctx.wants_keyboard_input());
+        return;
+    }
+    let Some(start) = drag.0 else {
+        return;
+    };
+
+    if mouse_button_input.pressed(MouseButton::Left) {
+        // show the box being dragged
+        if let Some(cursor_position) =
+            cursor_position.filter(|position| position.distance(start) > DRAG_THRESHOLD)
+        {
+            let painter = ctx.layer_painter(egui::LayerId::new(
+                egui::Order::Foreground,
+                egui::Id::new("selection_box"),
+            ));
+            painter.rect(
+                egui::Rect::from_two_pos(
+                    egui::pos2(start.x, start.y),
+                    egui::pos2(cursor_position.x, cursor_position.y),
+                ),
+                0.,
+                egui::Color32::from_rgba_unmultiplied(100, 150, 255, 30),
+                egui::Stroke::new(1., egui::Color32::LIGHT_BLUE),
+            );
+        }
+        return;
+    }
+
+    drag.0 = None;
+    let Some(cursor_position) = cursor_position else {
+        return;
+    };
+    let shift = shift_pressed(&keys);
+
+    if cursor_position.distance(start) > DRAG_THRESHOLD {
+        let (camera, camera_transform) = camera_query.single();
+        let area = Rect::from_corners(start, cursor_position);
+        for (entity, _, transform, selected) in &query {
+            let inside = camera
+                .world_to_viewport(camera_transform, transform.translation)
+                .is_some_and(|position| area.contains(position));
+            set_selected(&mut commands, entity, selected, inside || shift && selected);
+        }
+        return;
+    }
+
+    // clicking empty space deselects everything, unless Shift is held
+    for (entity, _, _, selected) in &query {
+        let select = if Some(entity) == hovered.0 {
+            !(shift && selected)
+        } else {
+            shift && selected
+        };
+        set_selected(&mut commands, entity, selected, select);
+    }
+}
+
+/// Despawns bodies, recording their deletion as a single edit.
+pub(super) fn delete_bodies(
+    commands: &mut Commands,
+    history: &mut EditHistory,
+    bodies: Vec<(Entity, BodySnapshot)>,
+) {
+    let label = match bodies.as_slice() {
+        [] => return,
+        [(_, snapshot)] => format!("Delete {}", snapshot.data.name),
+        _ => format!("Delete {} bodies", bodies.len()),
+    };
+    for (entity, _) in &bodies {
+        commands.entity(*entity).despawn_recursive();
+    }
+    info!("{}", label);
+    history.delete(label, bodies);
+}
+
+/// Deletes the selected bodies when the Delete key is pressed, unless a text
+/// field has the focus.
+pub(super) fn delete_selection_on_key(
+    mut contexts: EguiContexts,
+    mut commands: Commands,
+    keys: Res<ButtonInput<KeyCode>>,
+    query_selected: Query<
+        (Entity, &CelestialBodyData, &Position, &OrbitParent),
+        With<SelectedPlanetMarker>,
+    >,
+    mut history: ResMut<EditHistory>,
+) {
+    if !keys.just_pressed(KeyCode::Delete) || contexts.ctx_mut().wants_keyboard_input() {
+        return;
+    }
+    let bodies = query_selected
+        .iter()
+        .map(|(entity, data, position, orbit_parent)| {
+            (entity, BodySnapshot::new(data, position, orbit_parent))
+        })
+        .collect();
+    delete_bodies(&mut commands, &mut history, bodies);
+}